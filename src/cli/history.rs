@@ -0,0 +1,143 @@
+use alloc::{string::String, vec::Vec};
+
+/// The lines entered at the prompt, oldest first.
+///
+/// A line that is entered again moves to the end instead of being stored twice, and the oldest
+/// lines are dropped once there are more than `capacity`.
+#[derive(Debug, Clone)]
+pub struct History {
+    entries: Vec<String>,
+    capacity: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(History::DEFAULT_CAPACITY)
+    }
+}
+
+impl History {
+    pub const DEFAULT_CAPACITY: usize = 1000;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: vec![],
+            capacity,
+        }
+    }
+
+    /// Appends `line`, ignoring blank lines. Returns whether the history changed.
+    pub fn push(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        if line.is_empty() || self.entries.last().map(String::as_str) == Some(line) {
+            return false;
+        }
+
+        self.entries.retain(|entry| entry != line);
+        self.entries.push(line.into());
+        if self.entries.len() > self.capacity {
+            let excess = self.entries.len() - self.capacity;
+            self.entries.drain(..excess);
+        }
+
+        true
+    }
+
+    /// Replaces the history with lines loaded from storage, applying the same rules as `push`.
+    pub fn load(&mut self, lines: impl IntoIterator<Item = String>) {
+        self.entries.clear();
+        for line in lines {
+            self.push(&line);
+        }
+    }
+
+    #[inline]
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    /// Finds the newest entry before `before` that contains `query`, returning its index and the
+    /// character offset of the match.
+    pub fn search(&self, query: &str, before: usize) -> Option<(usize, usize)> {
+        self.entries[..before.min(self.entries.len())]
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, entry)| {
+                let offset = entry.find(query)?;
+                Some((index, entry[..offset].chars().count()))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(capacity: usize, lines: &[&str]) -> History {
+        let mut history = History::new(capacity);
+        for line in lines {
+            history.push(line);
+        }
+        history
+    }
+
+    #[test]
+    fn repeated_lines_are_stored_once() {
+        let mut history = history(10, &["ls", "ls", "pwd"]);
+        assert_eq!(history.entries(), ["ls", "pwd"]);
+        assert!(!history.push("pwd"));
+        // An older line moves to the end.
+        assert!(history.push("ls"));
+        assert_eq!(history.entries(), ["pwd", "ls"]);
+    }
+
+    #[test]
+    fn blank_lines_are_ignored() {
+        let mut history = history(10, &[]);
+        assert!(!history.push("   "));
+        assert!(history.push("  ls  "));
+        assert_eq!(history.entries(), ["ls"]);
+    }
+
+    #[test]
+    fn the_oldest_lines_are_dropped_past_the_capacity() {
+        let history = history(2, &["a", "b", "c"]);
+        assert_eq!(history.entries(), ["b", "c"]);
+        assert_eq!(history.get(0), Some("b"));
+        assert_eq!(history.get(2), None);
+    }
+
+    #[test]
+    fn loading_applies_the_same_rules() {
+        let mut history = history(2, &["old"]);
+        let lines = ["a", "a", "b", "c"].iter().map(|line| String::from(*line));
+        history.load(lines);
+        assert_eq!(history.entries(), ["b", "c"]);
+    }
+
+    #[test]
+    fn search_finds_the_newest_match_before_an_entry() {
+        let history = history(10, &["ls a", "cd b", "ls cc", "pwd"]);
+        assert_eq!(history.search("ls", history.len()), Some((2, 0)));
+        assert_eq!(history.search("ls", 2), Some((0, 0)));
+        assert_eq!(history.search("ls", 0), None);
+        assert_eq!(history.search("c", 10), Some((2, 3)));
+        assert_eq!(history.search("rm", history.len()), None);
+    }
+}
@@ -0,0 +1,903 @@
+use super::history::History;
+use crate::{
+    completion::{Completer, Suggestion},
+    shell::Shell,
+};
+use alloc::{collections::VecDeque, string::String, sync::Arc, vec::Vec};
+use core::fmt::Write;
+use spin::RwLock;
+
+/// A key press, decoded from the raw bytes of a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    /// Ctrl-C, abandons the line
+    Interrupt,
+    /// Ctrl-D, ends the input when the line is empty
+    EndOfInput,
+    /// Ctrl-K
+    KillToEnd,
+    /// Ctrl-U
+    KillToStart,
+    /// Ctrl-W
+    KillWord,
+    /// Ctrl-R, searches the history backwards
+    Search,
+    /// Ctrl-G, leaves a search without taking its match
+    Cancel,
+    Tab,
+}
+
+/// Where the decoder is within a multi-byte sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DecoderState {
+    Ground,
+    /// After `ESC`
+    Escape,
+    /// After `ESC [`, collecting the numeric parameters
+    Csi(Vec<u8>),
+    /// After `ESC O`
+    Ss3,
+    /// Inside a UTF-8 character, with the bytes so far and the total length
+    Utf8(Vec<u8>, usize),
+}
+
+/// Turns the bytes a VT100-compatible terminal sends into keys.
+#[derive(Debug, Clone)]
+pub struct KeyDecoder {
+    state: DecoderState,
+    /// Terminals send `\r`, `\n` or `\r\n` for enter, so a `\n` right after a `\r` is dropped.
+    after_cr: bool,
+}
+
+impl Default for KeyDecoder {
+    fn default() -> Self {
+        Self {
+            state: DecoderState::Ground,
+            after_cr: false,
+        }
+    }
+}
+
+impl KeyDecoder {
+    /// Feeds one byte to the decoder, returning a key once a whole one has been read.
+    pub fn decode(&mut self, byte: u8) -> Option<Key> {
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+        match core::mem::replace(&mut self.state, DecoderState::Ground) {
+            DecoderState::Ground => match byte {
+                b'\r' => Some(Key::Enter),
+                b'\n' if after_cr => None,
+                b'\n' => Some(Key::Enter),
+                0x01 => Some(Key::Home),
+                0x02 => Some(Key::Left),
+                0x03 => Some(Key::Interrupt),
+                0x04 => Some(Key::EndOfInput),
+                0x05 => Some(Key::End),
+                0x06 => Some(Key::Right),
+                0x07 => Some(Key::Cancel),
+                0x08 | 0x7f => Some(Key::Backspace),
+                0x09 => Some(Key::Tab),
+                0x0b => Some(Key::KillToEnd),
+                0x0e => Some(Key::Down),
+                0x10 => Some(Key::Up),
+                0x12 => Some(Key::Search),
+                0x15 => Some(Key::KillToStart),
+                0x17 => Some(Key::KillWord),
+                0x1b => {
+                    self.state = DecoderState::Escape;
+                    None
+                }
+                0x20..=0x7e => Some(Key::Char(byte as char)),
+                0xc0..=0xdf => self.start_utf8(byte, 2),
+                0xe0..=0xef => self.start_utf8(byte, 3),
+                0xf0..=0xf7 => self.start_utf8(byte, 4),
+                _ => None,
+            },
+            DecoderState::Escape => {
+                match byte {
+                    b'[' => self.state = DecoderState::Csi(vec![]),
+                    b'O' => self.state = DecoderState::Ss3,
+                    // Alt-modified keys aren't bound to anything.
+                    _ => {}
+                }
+                None
+            }
+            DecoderState::Csi(mut params) => match byte {
+                0x30..=0x3f => {
+                    params.push(byte);
+                    self.state = DecoderState::Csi(params);
+                    None
+                }
+                b'~' => {
+                    // `ESC [ 3 ~` style keys, possibly followed by `;` and modifiers
+                    let code = params.split(|&b| b == b';').next().unwrap_or(&[]);
+                    match code {
+                        b"1" | b"7" => Some(Key::Home),
+                        b"3" => Some(Key::Delete),
+                        b"4" | b"8" => Some(Key::End),
+                        _ => None,
+                    }
+                }
+                _ => cursor_key(byte),
+            },
+            DecoderState::Ss3 => cursor_key(byte),
+            DecoderState::Utf8(mut bytes, len) => {
+                if byte & 0xc0 != 0x80 {
+                    // A broken sequence, start over with this byte.
+                    return self.decode(byte);
+                }
+                bytes.push(byte);
+                if bytes.len() < len {
+                    self.state = DecoderState::Utf8(bytes, len);
+                    return None;
+                }
+                core::str::from_utf8(&bytes)
+                    .ok()
+                    .and_then(|s| s.chars().next())
+                    .map(Key::Char)
+            }
+        }
+    }
+
+    fn start_utf8(&mut self, byte: u8, len: usize) -> Option<Key> {
+        self.state = DecoderState::Utf8(vec![byte], len);
+        None
+    }
+}
+
+/// The final byte of `ESC [ x` and `ESC O x` sequences.
+fn cursor_key(byte: u8) -> Option<Key> {
+    match byte {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        _ => None,
+    }
+}
+
+/// What a key press did to the line being edited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The line was accepted with enter
+    Line(String),
+    /// The line was abandoned with Ctrl-C
+    Interrupted,
+    /// Ctrl-D was pressed on an empty line
+    EndOfInput,
+}
+
+/// An editable line with history, drawn with VT100 escape sequences.
+///
+/// The editor only deals in bytes: it is fed what the user typed and writes what the terminal
+/// should show, so the std terminal and a serial console share the same implementation. Every
+/// character is assumed to take up a single column.
+pub struct LineEditor {
+    prompt: String,
+    line: Vec<char>,
+    cursor: usize,
+    /// Where the terminal's cursor is, counted from the start of the line
+    column: usize,
+    history: Arc<RwLock<History>>,
+    /// The history entry being shown, `history.len()` meaning the line being typed
+    history_index: usize,
+    /// The line being typed, put aside while browsing the history
+    draft: Vec<char>,
+    search: Option<Search>,
+    completer: Option<Completer>,
+    completions: Option<Completions>,
+    decoder: KeyDecoder,
+    /// Input read past the end of the last line, eg when several lines are pasted at once
+    pending: VecDeque<u8>,
+}
+
+/// An incremental search through the history, started with Ctrl-R.
+#[derive(Debug)]
+struct Search {
+    query: String,
+    /// The history entry the current match is in, if anything matched yet
+    index: Option<usize>,
+    /// Whether the query was extended past the last match
+    failed: bool,
+    /// The line and cursor from before the search, restored when it is cancelled
+    original: (Vec<char>, usize),
+}
+
+/// The candidates a Tab with more than one completion listed, which the next Tabs cycle through.
+struct Completions {
+    suggestions: Vec<Suggestion>,
+    /// The candidate currently filled in, `None` while the line is still as it was typed
+    index: Option<usize>,
+    /// The line and cursor as they were typed
+    original: (Vec<char>, usize),
+}
+
+impl LineEditor {
+    pub fn new(history: Arc<RwLock<History>>) -> Self {
+        Self {
+            prompt: String::new(),
+            line: vec![],
+            cursor: 0,
+            column: 0,
+            history,
+            history_index: 0,
+            draft: vec![],
+            search: None,
+            completer: None,
+            completions: None,
+            decoder: KeyDecoder::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Completes the word under the cursor with `completer` when Tab is pressed.
+    pub fn with_completer(mut self, completer: Completer) -> Self {
+        self.completer = Some(completer);
+        self
+    }
+
+    /// Starts editing a new, empty line.
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.cursor = 0;
+        self.column = 0;
+        self.draft.clear();
+        self.search = None;
+        self.completions = None;
+        self.history_index = self.history.read().len();
+    }
+
+    /// Shows `prompt` and reads a line from `shell`, echoing and editing it as the keys come in.
+    /// Returns `None` once the input is closed or the user ends it with Ctrl-D, and a blank line
+    /// when Ctrl-C abandons it.
+    ///
+    /// The shell's input has to be in raw mode, the editor does all of the echoing itself.
+    pub async fn read_line<S: Shell + ?Sized>(
+        &mut self,
+        shell: &S,
+        prompt: &str,
+    ) -> Option<String> {
+        self.reset();
+        self.prompt = prompt.into();
+        shell.print(prompt);
+        loop {
+            if self.pending.is_empty() {
+                let input = shell.read_input().await;
+                if input.is_empty() {
+                    return None;
+                }
+                self.pending.extend(input);
+            }
+
+            let mut out = String::new();
+            while let Some(byte) = self.pending.pop_front() {
+                let event = match self.decoder.decode(byte) {
+                    Some(Key::Tab) => {
+                        self.complete(shell, &mut out).await;
+                        None
+                    }
+                    Some(key) => {
+                        self.completions = None;
+                        self.handle_key(key, &mut out)
+                    }
+                    None => None,
+                };
+                if let Some(event) = event {
+                    shell.print(&out);
+                    match event {
+                        Event::Line(line) => return Some(line),
+                        // The caller gets a blank line, so it prompts again.
+                        Event::Interrupted => return Some(String::new()),
+                        Event::EndOfInput => return None,
+                    }
+                }
+            }
+            shell.print(&out);
+        }
+    }
+
+    /// Applies a key to the line, writing the escape sequences that update the screen to `out`.
+    pub fn handle_key(&mut self, key: Key, out: &mut String) -> Option<Event> {
+        let key = match self.search {
+            Some(_) => self.handle_search_key(key, out)?,
+            None => key,
+        };
+
+        match key {
+            Key::Char(c) => {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+                if self.cursor == self.line.len() {
+                    out.push(c);
+                    self.column += 1;
+                } else {
+                    self.redraw(out);
+                }
+            }
+            Key::Enter => {
+                out.push_str("\r\n");
+                let line = self.line.iter().collect();
+                self.reset();
+                return Some(Event::Line(line));
+            }
+            Key::Interrupt => {
+                out.push_str("^C\r\n");
+                return Some(Event::Interrupted);
+            }
+            Key::EndOfInput if self.line.is_empty() => {
+                out.push_str("\r\n");
+                return Some(Event::EndOfInput);
+            }
+            Key::EndOfInput | Key::Delete => {
+                if self.cursor < self.line.len() {
+                    self.line.remove(self.cursor);
+                    self.redraw(out);
+                }
+            }
+            Key::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.line.remove(self.cursor);
+                    self.redraw(out);
+                }
+            }
+            Key::Left => {
+                if self.cursor > 0 {
+                    self.move_to(self.cursor - 1, out);
+                }
+            }
+            Key::Right => {
+                if self.cursor < self.line.len() {
+                    self.move_to(self.cursor + 1, out);
+                }
+            }
+            Key::Home => self.move_to(0, out),
+            Key::End => self.move_to(self.line.len(), out),
+            Key::KillToEnd => {
+                self.line.truncate(self.cursor);
+                out.push_str("\x1b[K");
+            }
+            Key::KillToStart => {
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+                self.redraw(out);
+            }
+            Key::KillWord => {
+                let mut start = self.cursor;
+                while start > 0 && self.line[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !self.line[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                self.line.drain(start..self.cursor);
+                self.cursor = start;
+                self.redraw(out);
+            }
+            Key::Up => {
+                if self.history_index > 0 {
+                    if self.history_index == self.history.read().len() {
+                        self.draft = core::mem::take(&mut self.line);
+                    }
+                    self.history_index -= 1;
+                    let entry = self.history_entry(self.history_index);
+                    self.show(entry.unwrap_or_default(), out);
+                }
+            }
+            Key::Down => {
+                if self.history_index < self.history.read().len() {
+                    self.history_index += 1;
+                    let line = match self.history_entry(self.history_index) {
+                        Some(entry) => entry,
+                        None => core::mem::take(&mut self.draft),
+                    };
+                    self.show(line, out);
+                }
+            }
+            Key::Search => {
+                self.search = Some(Search {
+                    query: String::new(),
+                    index: None,
+                    failed: false,
+                    original: (self.line.clone(), self.cursor),
+                });
+                self.redraw(out);
+            }
+            Key::Cancel | Key::Tab => {}
+        }
+
+        None
+    }
+
+    /// Applies a key to the running search. Keys that end the search are handed back, to be
+    /// applied to the line it found.
+    fn handle_search_key(&mut self, key: Key, out: &mut String) -> Option<Key> {
+        let search = self.search.as_mut()?;
+        let newest = self.history.read().len();
+        let before = match key {
+            Key::Char(c) => {
+                search.query.push(c);
+                search.index.map_or(newest, |index| index + 1)
+            }
+            Key::Backspace => {
+                search.query.pop();
+                newest
+            }
+            Key::Search => search.index.unwrap_or(newest),
+            Key::Cancel | Key::Interrupt => {
+                let (line, cursor) = core::mem::take(&mut search.original);
+                self.line = line;
+                self.cursor = cursor;
+                self.search = None;
+                self.redraw(out);
+                return (key == Key::Interrupt).then_some(key);
+            }
+            _ => {
+                if let Some(index) = search.index {
+                    self.history_index = index;
+                    self.draft = core::mem::take(&mut search.original.0);
+                }
+                self.search = None;
+                self.redraw(out);
+                return Some(key);
+            }
+        };
+
+        let found = match search.query.as_str() {
+            "" => None,
+            query => self.history.read().search(query, before),
+        };
+        search.failed = found.is_none() && !search.query.is_empty();
+        if let Some((index, offset)) = found {
+            search.index = Some(index);
+            self.line = self.history_entry(index).unwrap_or_default();
+            self.cursor = offset;
+        }
+        self.redraw(out);
+
+        None
+    }
+
+    /// Completes the word before the cursor. A single candidate is filled in, several are filled
+    /// in as far as they agree and listed below the line when that doesn't add anything. Pressing
+    /// Tab again after that cycles through them.
+    async fn complete<S: Shell + ?Sized>(&mut self, shell: &S, out: &mut String) {
+        if let Some(completions) = &mut self.completions {
+            let index = completions
+                .index
+                .map_or(0, |index| (index + 1) % completions.suggestions.len());
+            completions.index = Some(index);
+            let suggestion = completions.suggestions[index].clone();
+            let (line, cursor) = completions.original.clone();
+            self.line = line;
+            self.cursor = cursor;
+            self.apply(&suggestion, out);
+            return;
+        }
+
+        let completer = match &self.completer {
+            Some(completer) => completer,
+            None => return,
+        };
+        let line: String = self.line.iter().collect();
+        let pos = self.line[..self.cursor].iter().map(|c| c.len_utf8()).sum();
+        let suggestions = completer.complete(&line, pos).await;
+        match suggestions.as_slice() {
+            [] => {}
+            [suggestion] => self.apply(suggestion, out),
+            [first, ..] => {
+                let common = common_prefix(&suggestions);
+                if common.len() > first.span.len() {
+                    let span = first.span;
+                    self.apply(
+                        &Suggestion {
+                            replacement: common,
+                            span,
+                        },
+                        out,
+                    );
+                } else {
+                    self.list(&suggestions, shell.terminal_width(), out);
+                    self.completions = Some(Completions {
+                        suggestions,
+                        index: None,
+                        original: (self.line.clone(), self.cursor),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Replaces the part of the line the suggestion covers and moves the cursor after it.
+    fn apply(&mut self, suggestion: &Suggestion, out: &mut String) {
+        let line: String = self.line.iter().collect();
+        let start = suggestion.span.start().min(line.len());
+        let end = suggestion.span.end().min(line.len());
+        let mut chars: Vec<char> = line[..start].chars().collect();
+        chars.extend(suggestion.replacement.chars());
+        self.cursor = chars.len();
+        chars.extend(line[end..].chars());
+        self.line = chars;
+        self.redraw(out);
+    }
+
+    /// Prints the candidates in columns under the line, then the prompt and line again.
+    fn list(&mut self, suggestions: &[Suggestion], width: usize, out: &mut String) {
+        let names: Vec<&str> = suggestions
+            .iter()
+            .map(|suggestion| display_name(&suggestion.replacement))
+            .collect();
+        let column_width = names
+            .iter()
+            .map(|name| name.chars().count())
+            .max()
+            .unwrap_or(0)
+            + 2;
+        let columns = (width / column_width).max(1);
+
+        out.push_str("\r\n");
+        for (i, name) in names.iter().enumerate() {
+            if (i + 1) % columns == 0 || i + 1 == names.len() {
+                out.push_str(name);
+                out.push_str("\r\n");
+            } else {
+                let _ = write!(out, "{:width$}", name, width = column_width);
+            }
+        }
+        out.push_str(&self.prompt);
+        self.column = 0;
+        self.redraw(out);
+    }
+
+    fn history_entry(&self, index: usize) -> Option<Vec<char>> {
+        self.history
+            .read()
+            .get(index)
+            .map(|entry| entry.chars().collect())
+    }
+
+    /// Replaces the whole line, leaving the cursor at its end.
+    fn show(&mut self, line: Vec<char>, out: &mut String) {
+        self.line = line;
+        self.cursor = self.line.len();
+        self.redraw(out);
+    }
+
+    /// Rewrites the line from the start and puts the cursor back where it belongs.
+    fn redraw(&mut self, out: &mut String) {
+        let mut text = String::new();
+        let mut cursor = self.cursor;
+        if let Some(search) = &self.search {
+            let failed = if search.failed { "failed " } else { "" };
+            let _ = write!(text, "({}reverse-i-search)`{}': ", failed, search.query);
+            cursor += text.chars().count();
+        }
+        text.extend(self.line.iter());
+
+        move_cursor(self.column, 0, out);
+        out.push_str(&text);
+        out.push_str("\x1b[K");
+        move_cursor(text.chars().count(), cursor, out);
+        self.column = cursor;
+    }
+
+    fn move_to(&mut self, cursor: usize, out: &mut String) {
+        move_cursor(self.column, cursor, out);
+        self.cursor = cursor;
+        self.column = cursor;
+    }
+}
+
+/// The longest prefix all of the suggestions share.
+fn common_prefix(suggestions: &[Suggestion]) -> String {
+    let mut prefix: &str = suggestions
+        .first()
+        .map_or("", |suggestion| &suggestion.replacement);
+    for suggestion in suggestions {
+        let len = prefix
+            .char_indices()
+            .zip(suggestion.replacement.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(
+                prefix.len().min(suggestion.replacement.len()),
+                |((i, _), _)| i,
+            );
+        prefix = &prefix[..len];
+    }
+
+    prefix.into()
+}
+
+/// The last component of a path, which is all a listing needs to show.
+fn display_name(replacement: &str) -> &str {
+    match replacement.trim_end_matches('/').rfind('/') {
+        Some(i) => &replacement[i + 1..],
+        None => replacement,
+    }
+}
+
+fn move_cursor(from: usize, to: usize, out: &mut String) {
+    if to < from {
+        let _ = write!(out, "\x1b[{}D", from - to);
+    } else if to > from {
+        let _ = write!(out, "\x1b[{}C", to - from);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::Cd, context::CommandRegistry, shell::test::TestShell, stream::block_on};
+
+    fn keys(input: &[u8]) -> Vec<Key> {
+        let mut decoder = KeyDecoder::default();
+        input
+            .iter()
+            .filter_map(|&byte| decoder.decode(byte))
+            .collect()
+    }
+
+    fn editor(entries: &[&str]) -> LineEditor {
+        let mut history = History::default();
+        for entry in entries {
+            history.push(entry);
+        }
+        let mut editor = LineEditor::new(Arc::new(RwLock::new(history)));
+        editor.reset();
+        editor
+    }
+
+    /// Types `input` into the editor, returning the last event it caused.
+    fn type_in(editor: &mut LineEditor, input: &str) -> Option<Event> {
+        let mut out = String::new();
+        keys(input.as_bytes())
+            .into_iter()
+            .filter_map(|key| editor.handle_key(key, &mut out))
+            .last()
+    }
+
+    fn line(editor: &LineEditor) -> String {
+        editor.line.iter().collect()
+    }
+
+    #[test]
+    fn control_keys() {
+        assert_eq!(
+            keys(b"a\x01\x05\x03\x04\x7f\x08\t"),
+            [
+                Key::Char('a'),
+                Key::Home,
+                Key::End,
+                Key::Interrupt,
+                Key::EndOfInput,
+                Key::Backspace,
+                Key::Backspace,
+                Key::Tab
+            ]
+        );
+    }
+
+    #[test]
+    fn enter_is_read_once_whatever_the_terminal_sends() {
+        assert_eq!(keys(b"\r\n\n\r"), [Key::Enter, Key::Enter, Key::Enter]);
+    }
+
+    #[test]
+    fn cursor_keys() {
+        assert_eq!(
+            keys(b"\x1b[A\x1b[B\x1b[C\x1b[D\x1b[H\x1b[F"),
+            [
+                Key::Up,
+                Key::Down,
+                Key::Right,
+                Key::Left,
+                Key::Home,
+                Key::End
+            ]
+        );
+        assert_eq!(keys(b"\x1bOA\x1bOH\x1bOF"), [Key::Up, Key::Home, Key::End]);
+    }
+
+    #[test]
+    fn tilde_keys() {
+        assert_eq!(
+            keys(b"\x1b[1~\x1b[7~\x1b[4~\x1b[8~\x1b[3~"),
+            [Key::Home, Key::Home, Key::End, Key::End, Key::Delete]
+        );
+        // With modifiers
+        assert_eq!(keys(b"\x1b[3;5~"), [Key::Delete]);
+        // Unknown keys are skipped without eating what follows.
+        assert_eq!(keys(b"\x1b[15~x\x1bfy"), [Key::Char('x'), Key::Char('y')]);
+    }
+
+    #[test]
+    fn sequences_split_across_reads() {
+        let mut decoder = KeyDecoder::default();
+        assert_eq!(decoder.decode(0x1b), None);
+        assert_eq!(decoder.decode(b'['), None);
+        assert_eq!(decoder.decode(b'3'), None);
+        assert_eq!(decoder.decode(b'~'), Some(Key::Delete));
+        // é
+        assert_eq!(decoder.decode(0xc3), None);
+        assert_eq!(decoder.decode(0xa9), Some(Key::Char('é')));
+    }
+
+    #[test]
+    fn broken_utf8_starts_over() {
+        assert_eq!(keys(b"\xe2\x82x"), [Key::Char('x')]);
+    }
+
+    #[test]
+    fn typing_at_the_end_echoes_the_characters() {
+        let mut editor = editor(&[]);
+        let mut out = String::new();
+        editor.handle_key(Key::Char('a'), &mut out);
+        editor.handle_key(Key::Char('b'), &mut out);
+        assert_eq!(out, "ab");
+    }
+
+    #[test]
+    fn enter_hands_over_the_line() {
+        let mut editor = editor(&[]);
+        assert_eq!(
+            type_in(&mut editor, "ls -a\r"),
+            Some(Event::Line(String::from("ls -a")))
+        );
+        assert_eq!(line(&editor), "");
+    }
+
+    #[test]
+    fn inserting_and_deleting_inside_the_line() {
+        let mut editor = editor(&[]);
+        type_in(&mut editor, "helo\x1b[Dl");
+        assert_eq!(line(&editor), "hello");
+        type_in(&mut editor, "\x01\x1b[3~");
+        assert_eq!(line(&editor), "ello");
+        type_in(&mut editor, "\x05\x7f");
+        assert_eq!(line(&editor), "ell");
+        // Nothing to delete at either end
+        type_in(&mut editor, "\x1b[3~\x01\x7f");
+        assert_eq!(line(&editor), "ell");
+    }
+
+    #[test]
+    fn killing_parts_of_the_line() {
+        let mut editor = editor(&[]);
+        type_in(&mut editor, "cd some dir\x17");
+        assert_eq!(line(&editor), "cd some ");
+        type_in(&mut editor, "\x17");
+        assert_eq!(line(&editor), "cd ");
+        type_in(&mut editor, "a b\x1b[D\x1b[D\x0b");
+        assert_eq!(line(&editor), "cd a");
+        type_in(&mut editor, "\x1b[D\x15");
+        assert_eq!(line(&editor), "a");
+    }
+
+    #[test]
+    fn ctrl_c_and_ctrl_d() {
+        let mut interrupted = editor(&[]);
+        assert_eq!(
+            type_in(&mut interrupted, "ls\x03"),
+            Some(Event::Interrupted)
+        );
+        let mut editor = editor(&[]);
+        // Ctrl-D deletes unless the line is empty.
+        assert_eq!(type_in(&mut editor, "ls\x01\x04"), None);
+        assert_eq!(line(&editor), "s");
+        assert_eq!(type_in(&mut editor, "\x04"), None);
+        assert_eq!(type_in(&mut editor, "\x04"), Some(Event::EndOfInput));
+    }
+
+    #[test]
+    fn browsing_the_history_keeps_the_draft() {
+        let mut editor = editor(&["one", "two"]);
+        type_in(&mut editor, "dra");
+        type_in(&mut editor, "\x1b[A");
+        assert_eq!(line(&editor), "two");
+        type_in(&mut editor, "\x1b[A\x1b[A");
+        assert_eq!(line(&editor), "one");
+        type_in(&mut editor, "\x1b[B");
+        assert_eq!(line(&editor), "two");
+        type_in(&mut editor, "\x1b[B");
+        assert_eq!(line(&editor), "dra");
+    }
+
+    #[test]
+    fn reverse_search() {
+        let mut editor = editor(&["ls a", "cd b", "ls c"]);
+        type_in(&mut editor, "\x12ls");
+        assert_eq!(line(&editor), "ls c");
+        // Ctrl-R again finds an older match.
+        type_in(&mut editor, "\x12");
+        assert_eq!(line(&editor), "ls a");
+        assert_eq!(
+            type_in(&mut editor, "\r"),
+            Some(Event::Line(String::from("ls a")))
+        );
+    }
+
+    #[test]
+    fn cancelled_search_restores_the_line() {
+        let mut editor = editor(&["ls a"]);
+        type_in(&mut editor, "pwd\x12ls");
+        assert_eq!(line(&editor), "ls a");
+        type_in(&mut editor, "\x07");
+        assert_eq!(line(&editor), "pwd");
+        assert!(editor.search.is_none());
+    }
+
+    fn completing_editor(typed: &str) -> LineEditor {
+        let registry = CommandRegistry::empty();
+        registry.insert("cd", Arc::new(Cd));
+        let shell = TestShell::default()
+            .with_executables(&["cat", "cargo"])
+            .with_paths(&["src/", "src/main.rs"]);
+        let mut editor = editor(&[]).with_completer(Completer::new(registry, Arc::new(shell)));
+        type_in(&mut editor, typed);
+        editor
+    }
+
+    /// Presses Tab, returning what was written to the screen.
+    fn tab(editor: &mut LineEditor) -> String {
+        let mut out = String::new();
+        block_on(editor.complete(&TestShell::default(), &mut out));
+        out
+    }
+
+    #[test]
+    fn a_single_completion_is_filled_in() {
+        let mut editor = completing_editor("car");
+        tab(&mut editor);
+        assert_eq!(line(&editor), "cargo");
+        assert_eq!(editor.cursor, 5);
+    }
+
+    #[test]
+    fn completions_are_filled_in_as_far_as_they_agree() {
+        let mut editor = completing_editor("cd s");
+        tab(&mut editor);
+        assert_eq!(line(&editor), "cd src/");
+        assert!(editor.completions.is_none());
+    }
+
+    #[test]
+    fn completions_that_add_nothing_are_listed_and_cycled() {
+        let mut editor = completing_editor("ca");
+        let out = tab(&mut editor);
+        assert!(out.contains("cargo  cat\r\n"));
+        assert_eq!(line(&editor), "ca");
+        tab(&mut editor);
+        assert_eq!(line(&editor), "cargo");
+        tab(&mut editor);
+        assert_eq!(line(&editor), "cat");
+        tab(&mut editor);
+        assert_eq!(line(&editor), "cargo");
+    }
+
+    #[test]
+    fn common_prefix_of_suggestions() {
+        let suggestion = |replacement: &str| Suggestion {
+            replacement: String::from(replacement),
+            span: crate::parser::span::Span::new(0, 1),
+        };
+        let suggestions = [
+            suggestion("mkdir"),
+            suggestion("mkfifo"),
+            suggestion("mkdirs"),
+        ];
+        assert_eq!(common_prefix(&suggestions), "mk");
+        assert_eq!(common_prefix(&suggestions[..1]), "mkdir");
+        assert_eq!(common_prefix(&[]), "");
+    }
+}
@@ -1,39 +1,259 @@
-use alloc::sync::Arc;
+use alloc::{boxed::Box, string::String, sync::Arc};
 use core::sync::atomic::Ordering;
+use line_editor::LineEditor;
+use spin::RwLock;
 
 use crate::{
-    commands::{ CommandRef, Command},
+    commands::{run_pipeline, unhandled_failure, Command, CommandRef},
+    completion::Completer,
     context::Context,
-    shell::Shell,
     error::{ProximateShellError, ShellError},
-    parser,
-    parser::{
-        command::{
-            classified::{
-                external::{ExternalArgs, ExternalCommand},
-                internal::InternalCommand,
-                ClassifiedCommand, ClassifiedPipeline, Commands,
-            },
-            parse_command_tail,
-        },
-        span::HasSpan,
-        token::{SpannedToken, Token},
-    },
+    parser::{self, command::classified::ClassifiedPipeline, span::HasSpan},
+    render::{Renderer, StreamRenderer, FLUSH_AFTER},
+    shell::Shell,
+    stream::OutputStream,
 };
 
+pub mod history;
+pub mod line_editor;
 
 pub async fn cli(shell: Arc<dyn Shell>) -> Result<(), ShellError> {
     let mut context = create_default_context(shell.clone());
+    shell.interrupt_on_ctrl_c(context.ctrl_c.clone());
+    let completer = Completer::new(context.registry.clone(), shell.clone());
+    let mut editor = LineEditor::new(context.history.clone()).with_completer(completer);
+    match shell.load_history().await {
+        Ok(lines) => context.history.write().load(lines),
+        Err(err) => print_err(&err, "", 0, &shell),
+    }
 
     loop {
-        shell.print(&format!("{}> ", shell.path()));
-        let line = shell.readline().await;
-        shell.print(&line);
+        for job in context.jobs.write().take_finished() {
+            shell.print(&format!("[{}] {}  {}\n", job.id, job.state(), job.command));
+        }
+        let prompt = format!("{}> ", shell.path());
+        let mut source = match shell.readline(&prompt, &mut editor).await {
+            Some(line) => line,
+            None => break,
+        };
+        // Input that stops halfway, eg inside a quote, continues on the next lines. Their prompt
+        // is as wide as the first one, so that error positions still line up.
+        let continuation = format!("{:>width$}", "> ", width = prompt.chars().count());
+
+        context.ctrl_c.store(false, Ordering::Release);
+        let result = loop {
+            match process_line(&source, &mut context, prompt.chars().count()).await {
+                Err(err) if err.is_incomplete() => {
+                    match shell.readline(&continuation, &mut editor).await {
+                        Some(line) => {
+                            source.push('\n');
+                            source.push_str(&line);
+                        }
+                        None => break Err(err),
+                    }
+                }
+                result => break result,
+            }
+        };
+        if let Err(err) = result {
+            print_err(&err, &source, prompt.chars().count(), &shell);
+        }
+
+        // History entries are single lines, continued lines are joined back together and input
+        // that needs its newlines isn't kept.
+        let entry = source.replace("\\\n", " ");
+        if !entry.contains('\n') && context.history.write().push(&entry) {
+            let entries = context.history.read().entries().to_vec();
+            if let Err(err) = shell.save_history(entries).await {
+                print_err(&err, "", 0, &shell);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the pipelines of `line`, printing what they output and the errors of those that fail.
+///
+/// `&&` and `||` run their pipeline depending on whether the last one succeeded, while `;` moves on
+/// either way. The error of a failed pipeline is printed unless it is an exit status that a `||`
+/// handles. A pipeline that ends in `&` is started as a background job, which counts as a success.
+async fn process_line(
+    line: &str,
+    context: &mut Context,
+    prompt_width: usize,
+) -> Result<(), ShellError> {
+    let tokens = parser::parse(line)?;
+    // Aliases are appended to it as they are expanded.
+    let mut source = String::from(line);
+    let pipelines = parser::classify_pipeline_with_aliases(
+        tokens,
+        &context.registry,
+        &context.aliases.read(),
+        &mut source,
+    )?;
+    let mut failure = None;
+    for pipeline in pipelines {
+        if let Some(err) = unhandled_failure(&mut failure, pipeline.condition) {
+            print_err(&err, line, prompt_width, &context.shell);
+        }
+        if !pipeline.condition.runs_after(failure.is_none()) {
+            continue;
+        }
+        let result = if pipeline.background {
+            start_job(pipeline, context, &source).map(|()| OutputStream::empty())
+        } else {
+            run_pipeline(pipeline, context, &source, false).await
+        };
+        // What the blocks and substitutions of the pipeline moved past, before and while its
+        // output came out
+        print_current_errors(line, prompt_width, context);
+        let result = result.and_then(|output| render(output, context));
+        print_current_errors(line, prompt_width, context);
+        context.set_last_exit(result.as_ref().err());
+        failure = result.err();
+    }
+    if let Some(err) = failure {
+        print_err(&err, line, prompt_width, &context.shell);
+    }
+
+    Ok(())
+}
+
+/// Prints values as the pipeline produces them, until it is exhausted or interrupted. The rows
+/// held back for a table are printed whenever the output pauses.
+///
+/// Returns the failure of a command behind the stream, which is only known once it has run out.
+pub(crate) fn render(output: OutputStream, context: &Context) -> Result<(), ShellError> {
+    let shell = &context.shell;
+    let failures = output.failures();
+    let mut renderer = StreamRenderer::new(Renderer::for_shell(&**shell));
+    for value in shell.watch_stream(output, FLUSH_AFTER) {
+        if context.ctrl_c.load(Ordering::Acquire) {
+            break;
+        }
+        let text = match value {
+            Some(value) => renderer.push(value),
+            None => renderer.flush(),
+        };
+        if let Some(text) = text {
+            shell.print(&text);
+        }
+    }
+    if let Some(text) = renderer.finish() {
+        shell.print(&text);
     }
+    failures.check()
+}
+
+/// Starts `pipeline` as a background job, which runs with a context and ctrl-c flag of its own.
+/// It gets a copy of the variables, so that neither side sees what the other one sets.
+///
+/// What the job outputs is printed as it arrives, unless it is stopped. Its output is captured even
+/// when it ends in an external command, so that stopping and killing the job apply to that too.
+/// An error is printed under the command of the job, how it ended is reported before the next
+/// prompt.
+pub(crate) fn start_job(
+    pipeline: ClassifiedPipeline,
+    context: &Context,
+    source: &str,
+) -> Result<(), ShellError> {
+    let span = pipeline.span();
+    let job = context.jobs.write().add(span.string(source));
+    let id = job.id;
+    job.stop(&*context.shell)?;
+    let handle = job.clone();
+    let mut job_context = context.clone();
+    job_context.ctrl_c = job.ctrl_c.clone();
+    job_context.job = Some(job.clone());
+    job_context.scope = Arc::new(RwLock::new(context.scope.read().clone()));
+    job_context.current_errors = Arc::default();
+    let source = String::from(source);
+
+    let task = async move {
+        // Held back until the job number has been printed
+        job.running().await;
+        let shell = job_context.shell.clone();
+        let result = match run_pipeline(pipeline, &mut job_context, &source, true).await {
+            Ok(output) => {
+                let failures = output.failures();
+                let mut renderer = StreamRenderer::new(Renderer::for_shell(&*shell));
+                for value in shell.watch_stream(output, FLUSH_AFTER) {
+                    job.running().await;
+                    if job.ctrl_c.load(Ordering::Acquire) {
+                        break;
+                    }
+                    let text = match value {
+                        Some(value) => renderer.push(value),
+                        None => renderer.flush(),
+                    };
+                    if let Some(text) = text {
+                        shell.print(&text);
+                    }
+                }
+                if let Some(text) = renderer.finish() {
+                    shell.print(&text);
+                }
+                failures.check()
+            }
+            Err(err) => Err(err),
+        };
+        let mut errors = core::mem::take(&mut *job_context.current_errors.write());
+        let status = match result {
+            Ok(()) => 0,
+            Err(err) => {
+                let status = err.exit_status();
+                errors.push(err);
+                status
+            }
+        };
+        // A failed external command only gets its status reported.
+        for err in errors {
+            if !matches!(err.error, ProximateShellError::ExitStatus(..)) {
+                let prefix = format!("[{}] ", job.id);
+                shell.print(&format!("{}{}\n", prefix, job.command));
+                let err = err.shift_span(span.start());
+                print_err(&err, &job.command, prefix.chars().count(), &shell);
+            }
+        }
+        job.finish(status);
+    };
+    if let Err(err) = context.shell.spawn_job(Box::pin(task)) {
+        context.jobs.write().remove(id);
+        return Err(err);
+    }
+    context.shell.print(&format!("[{}]\n", id));
+    handle.resume(&*context.shell)?;
 
     Ok(())
 }
 
+fn print_current_errors(line: &str, prompt_width: usize, context: &Context) {
+    let errors = core::mem::take(&mut *context.current_errors.write());
+    for err in errors {
+        print_err(&err, line, prompt_width, &context.shell);
+    }
+}
+
+fn print_err(err: &ShellError, source: &str, prompt_width: usize, shell: &Arc<dyn Shell>) {
+    let indent = " ".repeat(prompt_width);
+    let message: String = match err.span() {
+        // Spanned errors point into the source, whose last line is still on screen right after
+        // the prompt. An error in an earlier line prints that line again.
+        Some(span) => {
+            let start = span.start().min(source.len());
+            let line_start = source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+            let line = match source[line_start..].find('\n') {
+                Some(line_end) => format!("{}{}\n", indent, &source[line_start..][..line_end]),
+                None => String::new(),
+            };
+            format!("{}{}{}", line, indent, err.clone().shift_span(line_start))
+        }
+        None => format!("error: {}", err),
+    };
+    shell.print(&format!("{}\n", message));
+}
+
 #[inline]
 fn create_default_context(shell: Arc<dyn Shell>) -> Context {
     let mut context = Context::new(shell);
@@ -48,6 +268,25 @@ fn create_default_context(shell: Arc<dyn Shell>) -> Context {
         context.add_commands(vec![
             command(Ls),
             command(Cd),
+            command(MkDir),
+            command(First),
+            command(HistoryCommand),
+            command(Let),
+            command(Length),
+            command(Pwd),
+            command(Select),
+            command(Jobs),
+            command(Fg),
+            command(Bg),
+            command(Kill),
+            command(If),
+            command(For),
+            command(While),
+            command(Def),
+            command(Help),
+            command(AliasCommand),
+            command(Unalias),
+            command(AliasesCommand),
         ])
     }
 
@@ -0,0 +1,95 @@
+use crate::{
+    commands::Command,
+    context::{self, Context},
+    error::ShellError,
+    evaluate::{CallInfo, Value},
+    parser::{self, syntax_shape::SyntaxShape, token::Token},
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::{boxed::Box, vec::Vec};
+
+pub struct AliasCommand;
+
+impl Command for AliasCommand {
+    fn name(&self) -> &str {
+        "alias"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("alias")
+            .required("name", SyntaxShape::String, "the name of the alias")
+            .required("equals", SyntaxShape::String, "the equals sign")
+            .rest_as_typed("the command that the alias stands for")
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Give a command, with some of its arguments, a shorter name."
+    }
+
+    // The command is kept as it was typed, so the arguments are read as they are.
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let args = call_info.args;
+            let name = match args.nth(0) {
+                Some(Value::String(name)) if !name.is_empty() => name.clone(),
+                _ => return Err(ShellError::runtime_error("alias needs a name")),
+            };
+            match args.nth(1) {
+                Some(Value::String(equals)) if equals == "=" => {}
+                _ => return Err(ShellError::runtime_error("expected `=` after the name")),
+            }
+            let text = match args.nth(2) {
+                Some(Value::String(text)) => text.clone(),
+                _ => return Err(ShellError::runtime_error("alias needs a command")),
+            };
+
+            let tokens: Vec<_> = parser::parse(&text)?
+                .item
+                .into_iter()
+                .filter(|token| token.item != Token::Whitespace)
+                .collect();
+            match tokens.first().map(|token| &token.item) {
+                None => return Err(ShellError::runtime_error("alias needs a command")),
+                Some(Token::Bare | Token::ExternalWord | Token::GlobPattern | Token::String(_)) => {
+                }
+                Some(_) => {
+                    return Err(ShellError::runtime_error(
+                        "an alias starts with the name of a command",
+                    ))
+                }
+            }
+            // Aliases are expanded one command at a time.
+            if let Some(token) = tokens.iter().find(|token| {
+                matches!(
+                    token.item,
+                    Token::Separator
+                        | Token::Pipe
+                        | Token::And
+                        | Token::Or
+                        | Token::Background
+                        | Token::Redirect(..)
+                )
+            }) {
+                return Err(ShellError::runtime_error(format!(
+                    "an alias stands for a single command, without a {}",
+                    token.item.desc()
+                )));
+            }
+
+            context
+                .aliases
+                .write()
+                .insert(name, context::Alias { text, tokens });
+
+            Ok(OutputStream::empty())
+        })
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::{CallInfo, Value},
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+pub struct AliasesCommand;
+
+impl Command for AliasesCommand {
+    fn name(&self) -> &str {
+        "aliases"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("aliases").desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "List the aliases and the commands they stand for."
+    }
+
+    fn run<'a>(
+        &'a self,
+        _call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        let rows: Vec<Value> = context
+            .aliases
+            .read()
+            .iter()
+            .map(|(name, alias)| {
+                Value::Record(indexmap::indexmap! {
+                    String::from("name") => Value::String(name.clone()),
+                    String::from("command") => Value::String(alias.text.clone()),
+                })
+            })
+            .collect();
+
+        Box::pin(async move { Ok(OutputStream::from(rows)) })
+    }
+}
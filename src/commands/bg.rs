@@ -0,0 +1,60 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::CallInfo,
+    parser::syntax_shape::SyntaxShape,
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::boxed::Box;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct BgArgs {
+    pub id: Option<usize>,
+}
+
+pub struct Bg;
+
+impl Command for Bg {
+    fn name(&self) -> &str {
+        "bg"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("bg")
+            .optional(
+                "id",
+                SyntaxShape::Int,
+                "the job to resume, the newest one by default",
+            )
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Resume a stopped job in the background."
+    }
+
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let BgArgs { id } = call_info.deserialize()?;
+            let job = context.jobs.read().find(id)?;
+            if !job.resume(&*context.shell)? {
+                return Err(ShellError::runtime_error(format!(
+                    "job {} is {}, not stopped",
+                    job.id,
+                    job.state()
+                )));
+            }
+
+            Ok(OutputStream::empty())
+        })
+    }
+}
@@ -1,16 +1,16 @@
-use alloc::{string::String, sync::Arc, vec::Vec};
-use core::sync::atomic::AtomicBool;
+use alloc::{boxed::Box, string::String};
 
 use serde::Deserialize;
 
 use crate::{
     commands::{Command, RunnableContext},
-    context::CommandRegistry,
+    context::Context,
     error::ShellError,
-    evaluate::{CallInfo, Value},
+    evaluate::CallInfo,
     parser::syntax_shape::SyntaxShape,
-    shell::Shell,
     signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
 };
 
 #[derive(Deserialize)]
@@ -39,18 +39,16 @@ impl Command for Cd {
         "Change to a new path."
     }
 
-    fn run(
-        &self,
+    fn run<'a>(
+        &'a self,
         call_info: CallInfo,
-        input: Option<Vec<Value>>,
-        ctrl_c: Arc<AtomicBool>,
-        shell: Arc<dyn Shell>,
-        _registry: &CommandRegistry,
-    ) -> Result<Option<Vec<Value>>, ShellError> {
-        call_info.process(&shell, ctrl_c, cd, input)?.run()
+        input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move { call_info.process(context, cd, input)?.run().await })
     }
 }
 
-fn cd(args: CdArgs, ctx: &RunnableContext) -> Result<Option<Vec<Value>>, ShellError> {
-    ctx.shell.cd(args)
+fn cd(args: CdArgs, ctx: RunnableContext) -> BoxFuture<'static, Result<OutputStream, ShellError>> {
+    Box::pin(async move { ctx.shell.cd(args).await })
 }
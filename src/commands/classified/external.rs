@@ -1,15 +1,61 @@
+use super::open_target;
 use crate::{
-    context::Context, error::ShellError, evaluate::Value,
+    context::Context,
+    error::{ProximateShellError, ShellError},
+    evaluate::evaluate_external_args,
     parser::command::classified::external::ExternalCommand,
+    shell::ExternalOutput,
+    stream::{Failure, InputStream, OutputStream},
 };
-use alloc::vec::Vec;
+use core::iter;
 
-#[allow(unused)]
-pub fn run_external_command(
-    command: ExternalCommand,
+pub async fn run_external_command(
+    mut command: ExternalCommand,
     context: &mut Context,
-    input: Option<Vec<Value>>,
+    input: Option<InputStream>,
     is_last: bool,
-) -> Result<Option<Vec<Value>>, ShellError> {
-    todo!()
+    source: &str,
+) -> Result<OutputStream, ShellError> {
+    command.args.list = evaluate_external_args(&command.args, context, source).await?;
+    let errors = match &command.errors {
+        Some(target) => Some(open_target(target, false, context, source).await?),
+        None => None,
+    };
+    // The output of a job is always captured, so that the processes it starts can be stopped and
+    // killed along with it.
+    let is_last = is_last && context.job.is_none();
+    match context
+        .shell
+        .run_external(&command, input, is_last, errors)
+        .await?
+    {
+        ExternalOutput::Exited(0) => Ok(OutputStream::empty()),
+        ExternalOutput::Exited(status) => {
+            Err(ProximateShellError::ExitStatus(command.name_span, status).start())
+        }
+        ExternalOutput::Stream(mut output, process) => {
+            if let Some(job) = &context.job {
+                job.add_process(&*context.shell, process.clone())?;
+            }
+            let exit = process.exit;
+            let failure = Failure::default();
+            let failures = output.failures();
+            let reported = failure.clone();
+            let name_span = command.name_span;
+            // The status only exists once the output has ended, so it is reported after the last
+            // value for the consumer to pick up.
+            let values = iter::from_fn(move || {
+                let value = output.next();
+                if value.is_none() {
+                    if let Some(status) = exit.get().filter(|&status| status != 0) {
+                        reported.set(ProximateShellError::ExitStatus(name_span, status).start());
+                    }
+                }
+                value
+            });
+            Ok(OutputStream::new(values)
+                .with_failures(failures)
+                .with_failure(failure))
+        }
+    }
 }
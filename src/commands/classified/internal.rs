@@ -1,16 +1,19 @@
 use crate::{
-    context::Context, error::ShellError, evaluate::Value,
+    context::Context,
+    error::ShellError,
     parser::command::classified::internal::InternalCommand,
+    stream::{InputStream, OutputStream},
 };
-use alloc::vec::Vec;
 
 #[inline]
-pub fn run_internal_command(
+pub async fn run_internal_command(
     command: InternalCommand,
     context: &mut Context,
-    input: Option<Vec<Value>>,
+    input: InputStream,
     source: &str,
-) -> Result<Option<Vec<Value>>, ShellError> {
+) -> Result<OutputStream, ShellError> {
     let internal_command = context.expect_command(command.name.as_str())?;
-    context.run_command(internal_command, command.args, source, input)
+    context
+        .run_command(internal_command, command.args, source, input)
+        .await
 }
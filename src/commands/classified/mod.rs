@@ -1,6 +1,28 @@
 mod external;
 mod internal;
-
+mod pipeline;
 
 pub use external::run_external_command;
-pub use internal::run_internal_command;
\ No newline at end of file
+pub use internal::run_internal_command;
+pub use pipeline::{run_pipeline, unhandled_failure};
+
+use crate::{
+    context::Context, error::ShellError, evaluate::evaluate_path, parser::token::SpannedToken,
+    shell::FileWriter,
+};
+use alloc::boxed::Box;
+
+/// Opens the file named by the `target` of a `>`, `>>` or `2>` redirection.
+async fn open_target(
+    target: &SpannedToken,
+    append: bool,
+    context: &Context,
+    source: &str,
+) -> Result<Box<dyn FileWriter>, ShellError> {
+    let path = evaluate_path(target, context, source).await?;
+    context
+        .shell
+        .open_write(&path, append)
+        .await
+        .map_err(|err| err.spanned(target.span))
+}
@@ -0,0 +1,111 @@
+use super::open_target;
+use crate::{
+    commands::classified::{run_external_command, run_internal_command},
+    context::Context,
+    error::{ProximateShellError, ShellError},
+    evaluate::evaluate_path,
+    parser::{
+        command::classified::{ClassifiedCommand, ClassifiedPipeline, Condition},
+        span::HasSpan,
+        token::Redirection,
+    },
+    render::Renderer,
+    stream::{Failures, OutputStream},
+};
+use core::sync::atomic::Ordering;
+
+/// Runs every stage of `pipeline`, handing the output of each stage to the next one as input.
+///
+/// Stages produce their values lazily, so the returned stream is what drives the pipeline. An
+/// external command at the end of the pipeline writes straight to the terminal, unless `capture`
+/// asks for its output to be streamed back like that of the other stages.
+///
+/// A `<` file is read as the input of the first stage. The output of a pipeline that ends in a
+/// `>` or `>>` is rendered into its file as plain text, leaving nothing to return.
+///
+/// A stage that only fails once its output has run out, like an external command exiting with a
+/// nonzero status, has its failure attached to the returned stream.
+///
+/// An error in the arguments of a command that came from an alias points at the alias.
+pub async fn run_pipeline(
+    mut pipeline: ClassifiedPipeline,
+    context: &mut Context,
+    source: &str,
+    capture: bool,
+) -> Result<OutputStream, ShellError> {
+    let mut input = match &pipeline.input {
+        Some(target) => {
+            let path = evaluate_path(target, context, source).await?;
+            let file = context.shell.open_read(&path).await;
+            Some(file.map_err(|err| err.spanned(target.span))?)
+        }
+        None => None,
+    };
+    let mut file = match &pipeline.output {
+        Some((redirection, target)) => {
+            let append = *redirection == Redirection::Append;
+            Some(open_target(target, append, context, source).await?)
+        }
+        None => None,
+    };
+
+    let capture = capture || file.is_some();
+    let expansions = core::mem::take(&mut pipeline.expansions);
+    let mut failures = Failures::default();
+    let mut commands = pipeline.commands.list.into_iter().peekable();
+    while let Some(command) = commands.next() {
+        if let Some(input) = &input {
+            failures.extend(input.failures());
+        }
+        let span = command.span();
+        let is_last = commands.peek().is_none() && !capture;
+        let output = match command {
+            ClassifiedCommand::Internal(command) => {
+                context.capture = !is_last;
+                run_internal_command(command, context, input.unwrap_or_default(), source).await
+            }
+            ClassifiedCommand::External(command) => {
+                run_external_command(command, context, input, is_last, source).await
+            }
+        }
+        .map_err(|err| {
+            let err = err.spanned(span);
+            expansions
+                .iter()
+                .fold(err, |err, expansion| expansion.locate(err))
+        })?;
+        input = Some(output);
+    }
+
+    let output = input.unwrap_or_default().with_failures(failures);
+    match &mut file {
+        Some(file) => {
+            let ctrl_c = &context.ctrl_c;
+            let failures = output.failures();
+            let values = output.take_while(|_| !ctrl_c.load(Ordering::Acquire));
+            Renderer::plain().render_stream(values, |text| file.write_str(text))?;
+            failures.check()?;
+            Ok(OutputStream::empty())
+        }
+        None => Ok(output),
+    }
+}
+
+/// Decides what becomes of `failure`, the error of the last pipeline that ran, now that the next
+/// pipeline with `condition` comes up, returning the error to report if there is one.
+///
+/// A `;` or newline moves past the failure, so it is reported and forgotten. A `||` handles it, so
+/// only an error other than an exit status is worth reporting.
+pub fn unhandled_failure(
+    failure: &mut Option<ShellError>,
+    condition: Condition,
+) -> Option<ShellError> {
+    match condition {
+        Condition::Always => failure.take(),
+        Condition::Succeeded => None,
+        Condition::Failed => failure
+            .as_ref()
+            .filter(|err| !matches!(err.error, ProximateShellError::ExitStatus(..)))
+            .cloned(),
+    }
+}
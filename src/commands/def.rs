@@ -0,0 +1,293 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::{run_block, Block, CallInfo, Value},
+    parser::{span::Span, syntax_shape::SyntaxShape},
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+};
+
+/// How deep calls of commands defined with `def` can nest, so that a command that calls itself
+/// without end fails instead of overflowing the stack. A call can take tens of kilobytes of stack
+/// in a debug build, and a spawned thread only has 2 MiB.
+const MAX_DEPTH: usize = 64;
+
+pub struct Def;
+
+impl Command for Def {
+    fn name(&self) -> &str {
+        "def"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("def")
+            .required("name", SyntaxShape::String, "the name of the command")
+            .required(
+                "params",
+                SyntaxShape::Any,
+                "the parameters, eg `[name: string, age?: int, ...rest, --loud]`",
+            )
+            .required("body", SyntaxShape::Block, "the block the command runs")
+            .named(
+                "usage",
+                SyntaxShape::String,
+                "what the command does, as help shows it",
+                None,
+            )
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Define a command that runs a block, with its arguments in variables."
+    }
+
+    // Blocks don't deserialize, so the arguments are read as they are.
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let args = call_info.args;
+            let name = match args.nth(0) {
+                Some(Value::String(name)) if !name.is_empty() => name.clone(),
+                _ => return Err(ShellError::runtime_error("def needs a command name")),
+            };
+            let params = match args.nth(1) {
+                Some(Value::List(params)) => params,
+                _ => return Err(ShellError::runtime_error("expected a list of parameters")),
+            };
+            let body = match args.nth(2) {
+                Some(Value::Block(body)) => body.clone(),
+                _ => return Err(ShellError::runtime_error("def needs a block to run")),
+            };
+            if let Some(command) = context.get_command(&name) {
+                if !command.is_custom() {
+                    return Err(ShellError::runtime_error(format!(
+                        "{} is a built-in command, which def can't replace",
+                        name
+                    )));
+                }
+            }
+            let (mut signature, rest) = parse_params(&name, params)?;
+            if let Some(Value::String(usage)) = args.get("usage") {
+                signature.usage = usage.clone();
+            }
+
+            let command = CustomCommand {
+                signature,
+                rest,
+                body,
+            };
+            context.registry.insert(name, Arc::new(command));
+
+            Ok(OutputStream::empty())
+        })
+    }
+}
+
+/// A command defined with `def`. Each call binds the arguments as variables in a frame of its
+/// own, named after the parameters, and runs the body.
+///
+/// The errors of the body point into the source of the `def`, so they are moved to the name of the
+/// command in the call.
+struct CustomCommand {
+    signature: Signature,
+    /// The name of the rest parameter, which `Signature` doesn't keep
+    rest: Option<String>,
+    body: Block,
+}
+
+impl Command for CustomCommand {
+    fn name(&self) -> &str {
+        &self.signature.name
+    }
+
+    fn signature(&self) -> Signature {
+        self.signature.clone()
+    }
+
+    fn usage(&self) -> &str {
+        &self.signature.usage
+    }
+
+    fn is_custom(&self) -> bool {
+        true
+    }
+
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let args = call_info.args;
+            let (body, call) = (Span::new(0, self.body.source.len()), call_info.name);
+            if context.depth >= MAX_DEPTH {
+                return Err(ShellError::labeled_error(
+                    format!(
+                        "calls of defined commands nest more than {} deep",
+                        MAX_DEPTH
+                    ),
+                    call,
+                ));
+            }
+            let positional = self.signature.positional.len();
+            let mut context = context.with_frame();
+            context.depth += 1;
+            {
+                let mut scope = context.scope.write();
+                for (index, (param, _)) in self.signature.positional.iter().enumerate() {
+                    let value = args.nth(index).cloned().unwrap_or(Value::Nothing);
+                    scope.set(param.name(), value);
+                }
+                if let Some(rest) = &self.rest {
+                    let values = if args.len() > positional {
+                        args.slice_from(positional)
+                    } else {
+                        vec![]
+                    };
+                    scope.set(rest.as_str(), Value::List(values));
+                }
+                for name in self.signature.named.keys() {
+                    let value = args.get(name).cloned().unwrap_or(Value::Nothing);
+                    scope.set(name.as_str(), value);
+                }
+            }
+            let output = run_block(&self.body, &context)
+                .await
+                .map_err(|err| err.relocate(body, call))?;
+
+            Ok(output.map_failure(move |err| err.relocate(body, call)))
+        })
+    }
+}
+
+/// Builds the signature of a command named `name` from the parameter list of `def`, returning the
+/// name of its rest parameter along with it.
+///
+/// A parameter is a name, optionally followed by `:` and the name of a `SyntaxShape`: a plain name
+/// is a required positional, `name?` an optional one, `...name` takes the rest of the positionals
+/// and `--name` is a switch, or a flag taking a value when it has a shape.
+fn parse_params(name: &str, params: &[Value]) -> Result<(Signature, Option<String>), ShellError> {
+    let mut words = vec![];
+    for param in params {
+        let text = match param {
+            Value::String(text) | Value::Path(text) | Value::Pattern(text) => text,
+            other => {
+                return Err(ShellError::runtime_error(format!(
+                    "expected a parameter, found {}",
+                    other
+                )))
+            }
+        };
+        // `name:`, `name:shape` and `name : shape` are all the same parameter. A word like `a?`
+        // is read as a pattern, which can keep the comma after it.
+        for text in text.split(',') {
+            for (index, part) in text.split(':').enumerate() {
+                if index > 0 {
+                    words.push(":");
+                }
+                if !part.is_empty() {
+                    words.push(part);
+                }
+            }
+        }
+    }
+
+    let mut signature = Signature::build(name);
+    let mut rest = None;
+    let mut words = words.into_iter().peekable();
+    while let Some(word) = words.next() {
+        if word == ":" {
+            return Err(ShellError::runtime_error("expected a parameter before `:`"));
+        }
+        let shape = if words.peek() == Some(&":") {
+            words.next();
+            let shape = words.next().ok_or_else(|| {
+                ShellError::runtime_error(format!("expected a type after `{}:`", word))
+            })?;
+            let shape = SyntaxShape::from_name(shape)
+                .ok_or_else(|| ShellError::runtime_error(format!("unknown type `{}`", shape)))?;
+            Some(shape)
+        } else {
+            None
+        };
+
+        let (kind, param) = if let Some(flag) = word.strip_prefix("--") {
+            ("--", flag)
+        } else if let Some(param) = word.strip_prefix("...") {
+            ("...", param)
+        } else if let Some(param) = word.strip_suffix('?') {
+            ("?", param)
+        } else {
+            ("", word)
+        };
+        if param.is_empty() || !param.chars().all(is_param_char) {
+            return Err(ShellError::runtime_error(format!(
+                "`{}` isn't a parameter name",
+                word
+            )));
+        }
+        let taken = signature
+            .positional
+            .iter()
+            .any(|(positional, _)| positional.name() == param)
+            || signature.named.contains_key(param)
+            || rest.as_deref() == Some(param);
+        if taken {
+            return Err(ShellError::runtime_error(format!(
+                "the parameter `{}` is already defined",
+                param
+            )));
+        }
+        if kind != "--" && rest.is_some() {
+            return Err(ShellError::runtime_error(
+                "only flags can follow the rest parameter",
+            ));
+        }
+
+        signature = match kind {
+            "--" => match shape {
+                Some(shape) => signature.named(param, shape, "", None),
+                None => signature.switch(param, "", None),
+            },
+            "..." => {
+                rest = Some(param.to_string());
+                let desc = format!("the rest of the arguments, as ${}", param);
+                signature.rest(shape.unwrap_or(SyntaxShape::Any), desc)
+            }
+            "?" => signature.optional(param, shape.unwrap_or(SyntaxShape::Any), ""),
+            _ => {
+                let after_optional = signature
+                    .positional
+                    .last()
+                    .is_some_and(|(positional, _)| positional.is_optional());
+                if after_optional {
+                    return Err(ShellError::runtime_error(format!(
+                        "the required parameter `{}` can't follow an optional one",
+                        param
+                    )));
+                }
+                signature.required(param, shape.unwrap_or(SyntaxShape::Any), "")
+            }
+        };
+    }
+
+    Ok((signature, rest))
+}
+
+/// Parameters are read as variables, so their names are made of the same characters.
+#[inline]
+fn is_param_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-')
+}
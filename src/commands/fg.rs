@@ -0,0 +1,64 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::CallInfo,
+    jobs::JobState,
+    parser::syntax_shape::SyntaxShape,
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::boxed::Box;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct FgArgs {
+    pub id: Option<usize>,
+}
+
+pub struct Fg;
+
+impl Command for Fg {
+    fn name(&self) -> &str {
+        "fg"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("fg")
+            .optional(
+                "id",
+                SyntaxShape::Int,
+                "the job to wait for, the newest one by default",
+            )
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Wait for a background job in the foreground, resuming it if it is stopped."
+    }
+
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let FgArgs { id } = call_info.deserialize()?;
+            let job = context.jobs.read().find(id)?;
+            job.resume(&*context.shell)?;
+            let state = job.finished().await;
+            // It has been waited for, so there is nothing left to report.
+            context.jobs.write().remove(job.id);
+
+            match state {
+                JobState::Done(0) => Ok(OutputStream::empty()),
+                state => Err(ShellError::runtime_error(format!(
+                    "job {} ended: {}",
+                    job.id, state
+                ))),
+            }
+        })
+    }
+}
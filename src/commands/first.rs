@@ -0,0 +1,56 @@
+use crate::{
+    commands::{Command, RunnableContext},
+    context::Context,
+    error::ShellError,
+    evaluate::CallInfo,
+    parser::syntax_shape::SyntaxShape,
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::boxed::Box;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct FirstArgs {
+    pub rows: Option<usize>,
+}
+
+pub struct First;
+
+impl Command for First {
+    fn name(&self) -> &str {
+        "first"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("first")
+            .optional(
+                "rows",
+                SyntaxShape::Int,
+                "starting from the front, the number of rows to return",
+            )
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Show only the first number of rows."
+    }
+
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move { call_info.process(context, first, input)?.run().await })
+    }
+}
+
+fn first(
+    FirstArgs { rows }: FirstArgs,
+    ctx: RunnableContext,
+) -> BoxFuture<'static, Result<OutputStream, ShellError>> {
+    let rows = rows.unwrap_or(1);
+    Box::pin(async move { Ok(OutputStream::new(ctx.input.take(rows))) })
+}
@@ -0,0 +1,72 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::{run_loop, CallInfo, Value},
+    parser::syntax_shape::SyntaxShape,
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::boxed::Box;
+
+pub struct For;
+
+impl Command for For {
+    fn name(&self) -> &str {
+        "for"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("for")
+            .required("name", SyntaxShape::String, "the name of the loop variable")
+            .required("in", SyntaxShape::String, "the word `in`")
+            .required("values", SyntaxShape::Any, "the list or table to loop over")
+            .required(
+                "body",
+                SyntaxShape::Block,
+                "the block to run for each value",
+            )
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Run a block once for each value of a list, with the value in a variable."
+    }
+
+    // Blocks don't deserialize, so the arguments are read as they are.
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let args = call_info.args;
+            let name = match args.nth(0) {
+                Some(Value::String(name)) if !name.is_empty() => name,
+                _ => return Err(ShellError::runtime_error("for needs a variable name")),
+            };
+            match args.nth(1) {
+                Some(Value::String(word)) if word == "in" => {}
+                _ => return Err(ShellError::runtime_error("expected `in` after the name")),
+            }
+            let values = match args.nth(2) {
+                Some(Value::List(values)) => values.clone(),
+                Some(Value::Table(rows)) => rows.iter().cloned().map(Value::Record).collect(),
+                Some(Value::Nothing) | None => vec![],
+                Some(value) => vec![value.clone()],
+            };
+            let body = match args.nth(3) {
+                Some(Value::Block(body)) => body,
+                _ => return Err(ShellError::runtime_error("for needs a block to run")),
+            };
+
+            let name = name.clone();
+            let mut values = values.into_iter();
+            Ok(run_loop(body, context, move |_| {
+                Ok(values.next().map(|value| vec![(name.clone(), value)]))
+            }))
+        })
+    }
+}
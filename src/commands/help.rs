@@ -0,0 +1,134 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::{CallInfo, Value},
+    parser::syntax_shape::SyntaxShape,
+    signature::{NamedType, PositionalType, Signature},
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt::Write;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct HelpArgs {
+    pub command: Option<String>,
+}
+
+pub struct Help;
+
+impl Command for Help {
+    fn name(&self) -> &str {
+        "help"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("help")
+            .optional(
+                "command",
+                SyntaxShape::String,
+                "the command to describe, instead of listing them all",
+            )
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "List the commands, or describe how to call one of them."
+    }
+
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let HelpArgs { command } = call_info.deserialize()?;
+            let registry = &context.registry;
+            let output = match command {
+                Some(name) => {
+                    let signature = registry.get(&name).ok_or_else(|| {
+                        ShellError::runtime_error(format!("there is no command {}", name))
+                    })?;
+                    vec![Value::String(describe(&signature))]
+                }
+                None => registry
+                    .names()
+                    .into_iter()
+                    .filter_map(|name| registry.get(&name))
+                    .map(|signature| {
+                        Value::Record(indexmap::indexmap! {
+                            String::from("name") => Value::String(signature.name),
+                            String::from("usage") => Value::String(signature.usage),
+                        })
+                    })
+                    .collect(),
+            };
+
+            Ok(OutputStream::from(output))
+        })
+    }
+}
+
+/// Writes out what `signature` says about its command: the usage, how it is called, and what each
+/// parameter is for.
+fn describe(signature: &Signature) -> String {
+    let mut call = signature.name.clone();
+    let mut params = Vec::new();
+    for (positional, desc) in &signature.positional {
+        let param = match positional {
+            PositionalType::Mandatory(name, _) => format!("<{}>", name),
+            PositionalType::Optional(name, _) => format!("[{}]", name),
+        };
+        call.push(' ');
+        call.push_str(&param);
+        params.push((param, positional.syntax_type(), desc));
+    }
+    if let Some((shape, desc)) = &signature.rest_positional {
+        call.push_str(" ...");
+        params.push((String::from("..."), *shape, desc));
+    }
+    if !signature.named.is_empty() {
+        call.push_str(" {flags}");
+    }
+
+    // Writing to a `String` can't fail.
+    let mut text = String::new();
+    if !signature.usage.is_empty() {
+        let _ = write!(text, "{}\n\n", signature.usage);
+    }
+    let _ = write!(text, "Usage:\n  {}\n", call);
+    if !params.is_empty() {
+        text.push_str("\nParameters:\n");
+        for (param, shape, desc) in params {
+            let line = format!("  {} ({}) {}", param, shape, desc);
+            let _ = writeln!(text, "{}", line.trim_end());
+        }
+    }
+    if !signature.named.is_empty() {
+        text.push_str("\nFlags:\n");
+        for (name, (kind, desc)) in &signature.named {
+            let _ = write!(text, "  --{}", name);
+            if let Some(short) = kind.short() {
+                let _ = write!(text, " (-{})", short);
+            }
+            match kind {
+                NamedType::Switch(_) => {}
+                NamedType::Mandatory(shape, _) => {
+                    let _ = write!(text, " <{}> (required)", shape);
+                }
+                NamedType::Optional(shape, _) => {
+                    let _ = write!(text, " <{}>", shape);
+                }
+            }
+            if !desc.is_empty() {
+                let _ = write!(text, " {}", desc);
+            }
+            text.push('\n');
+        }
+    }
+
+    String::from(text.trim_end())
+}
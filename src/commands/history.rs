@@ -0,0 +1,49 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::{CallInfo, Value},
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+pub struct HistoryCommand;
+
+impl Command for HistoryCommand {
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("history").desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Display the command history, oldest first."
+    }
+
+    fn run<'a>(
+        &'a self,
+        _call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        let rows: Vec<Value> = context
+            .history
+            .read()
+            .entries()
+            .iter()
+            .enumerate()
+            .map(|(index, command)| {
+                Value::Record(indexmap::indexmap! {
+                    String::from("index") => Value::Int(index.into()),
+                    String::from("command") => Value::String(command.clone()),
+                })
+            })
+            .collect();
+
+        Box::pin(async move { Ok(OutputStream::from(rows)) })
+    }
+}
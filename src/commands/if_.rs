@@ -0,0 +1,81 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::{is_true, run_block, CallInfo, Value},
+    parser::syntax_shape::SyntaxShape,
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::boxed::Box;
+
+pub struct If;
+
+impl Command for If {
+    fn name(&self) -> &str {
+        "if"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("if")
+            .required(
+                "condition",
+                SyntaxShape::Any,
+                "a value, or a block that is true when it succeeds",
+            )
+            .required(
+                "then",
+                SyntaxShape::Block,
+                "the block to run when the condition is true",
+            )
+            .optional("else", SyntaxShape::String, "the word `else`")
+            .optional(
+                "otherwise",
+                SyntaxShape::Block,
+                "the block to run when the condition is false",
+            )
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Run a block if a condition is true, or another one if it is false."
+    }
+
+    // Blocks don't deserialize, so the arguments are read as they are.
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let args = call_info.args;
+            let condition = args.nth(0).cloned().unwrap_or(Value::Nothing);
+            let otherwise = match (args.nth(2), args.nth(3)) {
+                (None, _) => None,
+                (Some(Value::String(word)), Some(Value::Block(block))) if word == "else" => {
+                    Some(block)
+                }
+                (Some(Value::String(word)), None) if word == "else" => {
+                    return Err(ShellError::runtime_error("expected a block after `else`"))
+                }
+                _ => return Err(ShellError::runtime_error("expected `else` after the block")),
+            };
+
+            let block = if is_true(&condition, context).await? {
+                match args.nth(1) {
+                    Some(Value::Block(block)) => block,
+                    _ => return Err(ShellError::runtime_error("if needs a block to run")),
+                }
+            } else {
+                match otherwise {
+                    Some(block) => block,
+                    None => return Ok(OutputStream::empty()),
+                }
+            };
+
+            run_block(block, context).await
+        })
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::{CallInfo, Value},
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+pub struct Jobs;
+
+impl Command for Jobs {
+    fn name(&self) -> &str {
+        "jobs"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("jobs").desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "List the jobs running in the background."
+    }
+
+    fn run<'a>(
+        &'a self,
+        _call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        let rows: Vec<Value> = context
+            .jobs
+            .read()
+            .jobs()
+            .iter()
+            .map(|job| {
+                Value::Record(indexmap::indexmap! {
+                    String::from("id") => Value::Int(job.id.into()),
+                    String::from("state") => Value::String(job.state().to_string()),
+                    String::from("command") => Value::String(job.command.clone()),
+                })
+            })
+            .collect();
+
+        Box::pin(async move { Ok(OutputStream::from(rows)) })
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::CallInfo,
+    parser::syntax_shape::SyntaxShape,
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::boxed::Box;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct KillArgs {
+    pub id: usize,
+    pub stop: bool,
+}
+
+pub struct Kill;
+
+impl Command for Kill {
+    fn name(&self) -> &str {
+        "kill"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("kill")
+            .required("id", SyntaxShape::Int, "the job to interrupt")
+            .switch(
+                "stop",
+                "pause the job instead, until bg or fg resumes it",
+                Some('s'),
+            )
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Interrupt a background job, like ctrl-c does for the foreground."
+    }
+
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let KillArgs { id, stop } = call_info.deserialize()?;
+            let job = context.jobs.read().find(Some(id))?;
+            if job.state().is_finished() {
+                return Err(ShellError::runtime_error(format!(
+                    "job {} has already finished",
+                    id
+                )));
+            }
+            if stop {
+                job.stop(&*context.shell)?;
+            } else {
+                job.kill(&*context.shell)?;
+            }
+
+            Ok(OutputStream::empty())
+        })
+    }
+}
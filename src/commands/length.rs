@@ -0,0 +1,42 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::{CallInfo, Value},
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::boxed::Box;
+use core::sync::atomic::Ordering;
+
+pub struct Length;
+
+impl Command for Length {
+    fn name(&self) -> &str {
+        "length"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("length").desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Show the total number of rows or items."
+    }
+
+    fn run<'a>(
+        &'a self,
+        _call_info: CallInfo,
+        input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        let ctrl_c = context.ctrl_c.clone();
+        Box::pin(async move {
+            let count = input
+                .take_while(|_| !ctrl_c.load(Ordering::Acquire))
+                .count();
+            Ok(OutputStream::one(Value::Int(count.into())))
+        })
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::{CallInfo, Value},
+    parser::syntax_shape::SyntaxShape,
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::boxed::Box;
+
+pub struct Let;
+
+impl Command for Let {
+    fn name(&self) -> &str {
+        "let"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("let")
+            .required("name", SyntaxShape::String, "the name of the variable")
+            .required("equals", SyntaxShape::String, "the equals sign")
+            .required("value", SyntaxShape::Any, "the value of the variable")
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Create a variable and give it a value."
+    }
+
+    // The value can be anything, so the arguments are read as they are instead of being
+    // deserialized.
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let args = call_info.args;
+            let name = match args.nth(0) {
+                Some(Value::String(name)) if !name.is_empty() => name.clone(),
+                _ => return Err(ShellError::runtime_error("let needs a variable name")),
+            };
+            match args.nth(1) {
+                Some(Value::String(equals)) if equals == "=" => {}
+                _ => return Err(ShellError::runtime_error("expected `=` after the name")),
+            }
+            let value = args.nth(2).cloned().unwrap_or(Value::Nothing);
+            context.scope.write().set(name, value);
+
+            Ok(OutputStream::empty())
+        })
+    }
+}
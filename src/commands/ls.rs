@@ -1,19 +1,20 @@
 use crate::{
     commands::{Command, RunnableContext},
-    context::CommandRegistry,
+    context::Context,
     error::ShellError,
-    evaluate::{CallInfo, Value},
+    evaluate::CallInfo,
     parser::syntax_shape::SyntaxShape,
-    shell::Shell,
     signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
 };
-use alloc::{string::String, sync::Arc, vec::Vec};
-use core::sync::atomic::AtomicBool;
+use alloc::{boxed::Box, string::String};
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug)]
 pub struct LsArgs {
     pub path: Option<String>,
+    pub all: bool,
 }
 
 pub struct Ls;
@@ -30,6 +31,7 @@ impl Command for Ls {
                 SyntaxShape::Pattern,
                 "a path to get the directory contents from",
             )
+            .switch("all", "show hidden files", Some('a'))
             .desc(self.usage())
     }
 
@@ -37,19 +39,20 @@ impl Command for Ls {
         "View the contents of the current or given path."
     }
 
-    fn run(
-        &self,
+    fn run<'a>(
+        &'a self,
         call_info: CallInfo,
-        input: Option<Vec<Value>>,
-        ctrl_c: Arc<AtomicBool>,
-        shell: Arc<dyn Shell>,
-        _registry: &CommandRegistry,
-    ) -> Result<Option<Vec<Value>>, ShellError> {
-        call_info.process(&shell, ctrl_c, ls, input)?.run()
+        input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move { call_info.process(context, ls, input)?.run().await })
     }
 }
 
-#[inline]
-fn ls(args: LsArgs, ctx: &RunnableContext) -> Result<Option<Vec<Value>>, ShellError> {
-    ctx.shell.ls(args, ctx)
+fn ls(args: LsArgs, ctx: RunnableContext) -> BoxFuture<'static, Result<OutputStream, ShellError>> {
+    Box::pin(async move {
+        let shell = ctx.shell.clone();
+        let listing = shell.ls(args, &ctx);
+        listing.await
+    })
 }
@@ -1,19 +1,20 @@
 use crate::{
     commands::{Command, RunnableContext},
-    context::CommandRegistry,
+    context::Context,
     error::ShellError,
-    evaluate::{CallInfo, Value},
+    evaluate::CallInfo,
     parser::syntax_shape::SyntaxShape,
-    shell::Shell,
     signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
 };
-use alloc::{string::String, sync::Arc, vec::Vec};
-use core::sync::atomic::AtomicBool;
+use alloc::{boxed::Box, string::String, vec::Vec};
 use serde::Deserialize;
 
 #[derive(Deserialize)]
 pub struct MkDirArgs {
     pub rest: Vec<String>,
+    pub parents: bool,
 }
 
 pub struct MkDir;
@@ -24,27 +25,33 @@ impl Command for MkDir {
     }
 
     fn usage(&self) -> &str {
-        "Make directories, creates intermediary directories as required."
+        "Make directories."
     }
 
     fn signature(&self) -> Signature {
         Signature::build("mkdir")
             .rest(SyntaxShape::Path, "the name of the path to create")
+            .switch(
+                "parents",
+                "create intermediary directories as required, and allow existing ones",
+                Some('p'),
+            )
             .desc(self.usage())
     }
 
-    fn run(
-        &self,
+    fn run<'a>(
+        &'a self,
         call_info: CallInfo,
-        input: Option<Vec<Value>>,
-        ctrl_c: Arc<AtomicBool>,
-        shell: Arc<dyn Shell>,
-        _registry: &CommandRegistry,
-    ) -> Result<Option<Vec<Value>>, ShellError> {
-        call_info.process(&shell, ctrl_c, mkdir, input)?.run()
+        input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move { call_info.process(context, mkdir, input)?.run().await })
     }
 }
 
-fn mkdir(args: MkDirArgs, ctx: &RunnableContext) -> Result<Option<Vec<Value>>, ShellError> {
-    ctx.shell.mkdir(args)
+fn mkdir(
+    args: MkDirArgs,
+    ctx: RunnableContext,
+) -> BoxFuture<'static, Result<OutputStream, ShellError>> {
+    Box::pin(async move { ctx.shell.mkdir(args).await })
 }
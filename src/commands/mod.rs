@@ -1,24 +1,62 @@
 use crate::{
-    context::CommandRegistry,
+    context::Context,
     deserializer::ConfigDeserializer,
     error::ShellError,
-    evaluate::{CallInfo, Value},
+    evaluate::CallInfo,
     shell::Shell,
     signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
 };
-use alloc::{sync::Arc, vec::Vec};
+use alloc::sync::Arc;
 use core::sync::atomic::AtomicBool;
 use serde::Deserialize;
 
+mod alias;
+mod aliases;
+mod bg;
 mod cd;
+mod classified;
+mod def;
+mod fg;
+mod first;
+mod for_;
+mod help;
+mod history;
+mod if_;
+mod jobs;
+mod kill;
+mod length;
+mod let_;
 mod ls;
 mod mkdir;
-mod classified;
+mod pwd;
+mod select;
+mod unalias;
+mod while_;
 
+pub use alias::AliasCommand;
+pub use aliases::AliasesCommand;
+pub use bg::{Bg, BgArgs};
 pub use cd::{Cd, CdArgs};
+pub use classified::{run_external_command, run_internal_command, run_pipeline, unhandled_failure};
+pub use def::Def;
+pub use fg::{Fg, FgArgs};
+pub use first::{First, FirstArgs};
+pub use for_::For;
+pub use help::{Help, HelpArgs};
+pub use history::HistoryCommand;
+pub use if_::If;
+pub use jobs::Jobs;
+pub use kill::{Kill, KillArgs};
+pub use length::Length;
+pub use let_::Let;
 pub use ls::{Ls, LsArgs};
 pub use mkdir::{MkDir, MkDirArgs};
-pub use classified::{run_external_command, run_internal_command};
+pub use pwd::Pwd;
+pub use select::{Select, SelectArgs};
+pub use unalias::{Unalias, UnaliasArgs};
+pub use while_::While;
 
 pub trait Command: Send + Sync {
     fn name(&self) -> &str;
@@ -29,29 +67,34 @@ pub trait Command: Send + Sync {
 
     fn usage(&self) -> &str;
 
-    fn run(
-        &self,
+    fn run<'a>(
+        &'a self,
         call_info: CallInfo,
-        input: Option<Vec<Value>>,
-        ctrl_c: Arc<AtomicBool>,
-        shell: Arc<dyn Shell>,
-        registry: &CommandRegistry,
-    ) -> Result<Option<Vec<Value>>, ShellError>;
+        input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>>;
 
     fn is_binary(&self) -> bool {
         false
     }
+
+    /// Whether the command was defined with `def`, which can define it again. Built-in commands
+    /// can't be replaced.
+    fn is_custom(&self) -> bool {
+        false
+    }
 }
 
 pub type CommandRef = Arc<dyn Command>;
 
 pub struct RunnableContext {
-    pub input: Option<Vec<Value>>,
+    pub input: InputStream,
     pub shell: Arc<dyn Shell>,
     pub ctrl_c: Arc<AtomicBool>,
 }
 
-pub type CommandCallback<T> = fn(T, &RunnableContext) -> Result<Option<Vec<Value>>, ShellError>;
+pub type CommandCallback<T> =
+    fn(T, RunnableContext) -> BoxFuture<'static, Result<OutputStream, ShellError>>;
 
 pub struct RunnableArgs<T> {
     args: T,
@@ -61,25 +104,30 @@ pub struct RunnableArgs<T> {
 
 impl<T> RunnableArgs<T> {
     #[inline]
-    pub fn run(self) -> Result<Option<Vec<Value>>, ShellError> {
-        (self.callback)(self.args, &self.context)
+    pub async fn run(self) -> Result<OutputStream, ShellError> {
+        (self.callback)(self.args, self.context).await
     }
 }
 
 impl CallInfo {
+    /// Reads the arguments into `T`, for commands that need more of the `Context` than `process`
+    /// hands to its callback.
+    pub(crate) fn deserialize<'de, T: Deserialize<'de>>(&self) -> Result<T, ShellError> {
+        let mut deserializer = ConfigDeserializer::from_call_info(self.clone());
+        T::deserialize(&mut deserializer)
+    }
+
     pub(crate) fn process<'de, T: Deserialize<'de>>(
         &self,
-        shell: &Arc<dyn Shell>,
-        ctrl_c: Arc<AtomicBool>,
+        context: &Context,
         callback: CommandCallback<T>,
-        input: Option<Vec<Value>>,
+        input: InputStream,
     ) -> Result<RunnableArgs<T>, ShellError> {
-        let mut deserializer = ConfigDeserializer::from_call_info(self.clone());
         Ok(RunnableArgs {
-            args: T::deserialize(&mut deserializer)?,
+            args: self.deserialize()?,
             context: RunnableContext {
-                shell: shell.clone(),
-                ctrl_c,
+                shell: context.shell.clone(),
+                ctrl_c: context.ctrl_c.clone(),
                 input,
             },
             callback,
@@ -0,0 +1,36 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::{CallInfo, Value},
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::boxed::Box;
+
+pub struct Pwd;
+
+impl Command for Pwd {
+    fn name(&self) -> &str {
+        "pwd"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("pwd").desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Output the current working directory."
+    }
+
+    fn run<'a>(
+        &'a self,
+        _call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        let path = context.shell.path();
+        Box::pin(async move { Ok(OutputStream::one(Value::Path(path))) })
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{
+    commands::{Command, RunnableContext},
+    context::Context,
+    error::ShellError,
+    evaluate::{value::Record, CallInfo, Value},
+    parser::syntax_shape::SyntaxShape,
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct SelectArgs {
+    pub rest: Vec<String>,
+}
+
+pub struct Select;
+
+impl Command for Select {
+    fn name(&self) -> &str {
+        "select"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("select")
+            .rest(SyntaxShape::String, "the names of columns to select")
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Down-select table to only these columns."
+    }
+
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move { call_info.process(context, select, input)?.run().await })
+    }
+}
+
+fn select(
+    SelectArgs { rest: columns }: SelectArgs,
+    ctx: RunnableContext,
+) -> BoxFuture<'static, Result<OutputStream, ShellError>> {
+    Box::pin(async move {
+        if columns.is_empty() {
+            return Err(ShellError::runtime_error("select requires a column name"));
+        }
+
+        let pick = move |record: Record| -> Record {
+            columns
+                .iter()
+                .map(|column| {
+                    let value = record.get(column).cloned().unwrap_or(Value::Nothing);
+                    (column.clone(), value)
+                })
+                .collect()
+        };
+
+        Ok(OutputStream::new(ctx.input.filter_map(
+            move |value| match value {
+                Value::Record(record) => Some(Value::Record(pick(record))),
+                Value::Table(rows) => Some(Value::Table(rows.into_iter().map(&pick).collect())),
+                _ => None,
+            },
+        )))
+    })
+}
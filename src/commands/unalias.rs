@@ -0,0 +1,53 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::CallInfo,
+    parser::syntax_shape::SyntaxShape,
+    signature::Signature,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::{boxed::Box, string::String};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct UnaliasArgs {
+    pub name: String,
+}
+
+pub struct Unalias;
+
+impl Command for Unalias {
+    fn name(&self) -> &str {
+        "unalias"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("unalias")
+            .required("name", SyntaxShape::String, "the alias to remove")
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Remove an alias."
+    }
+
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let UnaliasArgs { name } = call_info.deserialize()?;
+            match context.aliases.write().shift_remove(&name) {
+                Some(_) => Ok(OutputStream::empty()),
+                None => Err(ShellError::runtime_error(format!(
+                    "there is no alias {}",
+                    name
+                ))),
+            }
+        })
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    commands::Command,
+    context::Context,
+    error::ShellError,
+    evaluate::{is_true, run_loop, CallInfo, Value},
+    parser::syntax_shape::SyntaxShape,
+    signature::Signature,
+    stream::block_on,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use alloc::{boxed::Box, vec::Vec};
+
+pub struct While;
+
+impl Command for While {
+    fn name(&self) -> &str {
+        "while"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("while")
+            .required(
+                "condition",
+                SyntaxShape::Block,
+                "the block checked before each iteration, true when it succeeds",
+            )
+            .required(
+                "body",
+                SyntaxShape::Block,
+                "the block to run while the condition is true",
+            )
+            .desc(self.usage())
+    }
+
+    fn usage(&self) -> &str {
+        "Run a block for as long as a condition is true."
+    }
+
+    // Blocks don't deserialize, so the arguments are read as they are.
+    fn run<'a>(
+        &'a self,
+        call_info: CallInfo,
+        _input: InputStream,
+        context: &'a Context,
+    ) -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+        Box::pin(async move {
+            let args = call_info.args;
+            // The condition is checked again before each iteration, so it has to be a block to
+            // have a chance to change.
+            let condition = match args.nth(0) {
+                Some(condition @ Value::Block(_)) => condition.clone(),
+                _ => {
+                    return Err(ShellError::runtime_error(
+                        "while needs a block as its condition, eg while { ... } { ... }",
+                    ))
+                }
+            };
+            let body = match args.nth(1) {
+                Some(Value::Block(body)) => body,
+                _ => return Err(ShellError::runtime_error("while needs a block to run")),
+            };
+
+            Ok(run_loop(body, context, move |context| {
+                let run = block_on(is_true(&condition, context))?;
+                Ok(run.then(Vec::new))
+            }))
+        })
+    }
+}
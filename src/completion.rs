@@ -0,0 +1,254 @@
+use crate::{
+    context::CommandRegistry,
+    parser::{span::Span, syntax_shape::SyntaxShape},
+    shell::Shell,
+    signature::{NamedType, Signature},
+};
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+/// A possible completion of the word under the cursor.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Suggestion {
+    /// The text that replaces `span`
+    pub replacement: String,
+    /// The part of the line being completed, from the start of the word up to the cursor
+    pub span: Span,
+}
+
+/// Suggests command names, flags and paths for a partially typed line.
+///
+/// What is suggested depends on where the cursor is: the first word of a pipeline stage is a
+/// command name, a word starting with `-` is a flag of that command, and any other word is one of
+/// its positionals, completed as a path when its `SyntaxShape` is a path or a pattern. External
+/// commands get paths for all of their arguments, and so does the file after a redirection.
+#[derive(Clone)]
+pub struct Completer {
+    registry: CommandRegistry,
+    shell: Arc<dyn Shell>,
+}
+
+impl Completer {
+    pub fn new(registry: CommandRegistry, shell: Arc<dyn Shell>) -> Self {
+        Self { registry, shell }
+    }
+
+    /// Returns the completions for the word that ends at the byte offset `pos` of `line`, sorted
+    /// and without duplicates.
+    pub async fn complete(&self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let mut pos = pos.min(line.len());
+        while !line.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        let words = stage_words(&line[..pos]);
+        let (span, word, previous) = match words.split_last() {
+            Some((&(span, word), previous)) if span.end() == pos => (span, word, previous),
+            _ => (Span::new(pos, pos), "", &words[..]),
+        };
+        // A quote opened before the word is kept, only what follows it is matched.
+        let quote = word.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let partial = &word[quote.map_or(0, char::len_utf8)..];
+
+        let mut candidates = match previous.split_first() {
+            None => self.commands(partial).await,
+            Some(_)
+                if previous
+                    .last()
+                    .is_some_and(|(_, word)| is_redirection(word)) =>
+            {
+                self.shell.complete_path(partial).await
+            }
+            Some((&(_, command), args)) => match self.registry.get(command) {
+                Some(signature) if partial.starts_with('-') => flags(&signature, partial),
+                Some(signature) => match positional_shape(&signature, args) {
+                    Some(SyntaxShape::Path) | Some(SyntaxShape::Pattern) => {
+                        self.shell.complete_path(partial).await
+                    }
+                    _ => vec![],
+                },
+                None => self.shell.complete_path(partial).await,
+            },
+        };
+        candidates.sort();
+        candidates.dedup();
+
+        candidates
+            .into_iter()
+            .map(|candidate| {
+                let mut replacement: String = quote.into_iter().collect();
+                replacement.push_str(&candidate);
+                Suggestion { replacement, span }
+            })
+            .collect()
+    }
+
+    async fn commands(&self, partial: &str) -> Vec<String> {
+        let mut names = self.registry.names();
+        names.extend(self.shell.executables().await);
+        names.retain(|name| name.starts_with(partial));
+        names
+    }
+}
+
+/// The `--flags` of `signature` that start with `partial`.
+fn flags(signature: &Signature, partial: &str) -> Vec<String> {
+    signature
+        .named
+        .keys()
+        .map(|name| format!("--{}", name))
+        .filter(|flag| flag.starts_with(partial))
+        .collect()
+}
+
+/// The shape of the positional that follows the arguments in `previous`, skipping flags and the
+/// values they take.
+fn positional_shape(signature: &Signature, previous: &[(Span, &str)]) -> Option<SyntaxShape> {
+    let mut index = 0;
+    let mut words = previous.iter();
+    while let Some((_, word)) = words.next() {
+        let kind = if is_redirection(word) {
+            // The file it names isn't an argument.
+            words.next();
+            None
+        } else if let Some(flag) = word.strip_prefix("--") {
+            // `--name=value` carries its own value
+            signature
+                .named
+                .get(flag)
+                .map(|(kind, _)| kind)
+                .filter(|_| !flag.contains('='))
+        } else if is_short_flags(word) {
+            word.chars()
+                .last()
+                .and_then(|short| signature.short_named(short))
+                .map(|(_, kind)| kind)
+        } else {
+            index += 1;
+            None
+        };
+        if let Some(NamedType::Mandatory(..)) | Some(NamedType::Optional(..)) = kind {
+            words.next();
+        }
+    }
+
+    signature.positional_shape(index)
+}
+
+/// Whether `word` is a redirection operator, followed by the file it names.
+fn is_redirection(word: &str) -> bool {
+    matches!(word, "<" | ">" | ">>" | "2>")
+}
+
+/// Whether `word` is a cluster of single character flags, like `-la`.
+fn is_short_flags(word: &str) -> bool {
+    match word.strip_prefix('-') {
+        Some(flags) => !flags.is_empty() && flags.chars().all(char::is_alphabetic),
+        None => false,
+    }
+}
+
+/// Splits the last pipeline stage of `line` into words, the same way the tokenizer separates
+/// them: on whitespace, `|`, `;`, `&`, parentheses and braces, except inside quotes, where a backslash
+/// escapes the next character of a double quoted string. An unterminated quote runs to the end of
+/// the line, an unclosed `(` or `{` starts a new pipeline.
+fn stage_words(line: &str) -> Vec<(Span, &str)> {
+    let mut words = vec![];
+    let mut start = None;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some('"'), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, c)
+                if c.is_whitespace() || matches!(c, '|' | ';' | '&' | '(' | ')' | '{' | '}') =>
+            {
+                if let Some(start) = start.take() {
+                    words.push((Span::new(start, i), &line[start..i]));
+                }
+                if matches!(c, '|' | ';' | '&' | '(' | '{') {
+                    words.clear();
+                }
+            }
+            (None, c) => {
+                if c == '"' || c == '\'' {
+                    quote = Some(c);
+                }
+                start.get_or_insert(i);
+            }
+        }
+    }
+    if let Some(start) = start {
+        words.push((Span::new(start, line.len()), &line[start..]));
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::{Cd, Ls, MkDir},
+        shell::test::TestShell,
+        stream::block_on,
+    };
+
+    fn completer() -> Completer {
+        let registry = CommandRegistry::empty();
+        registry.insert("ls", Arc::new(Ls));
+        registry.insert("cd", Arc::new(Cd));
+        registry.insert("mkdir", Arc::new(MkDir));
+        let shell = TestShell::default()
+            .with_executables(&["cat", "cargo", "lsblk"])
+            .with_paths(&["src/", "src/main.rs", "Cargo.toml", "target/"]);
+        Completer::new(registry, Arc::new(shell))
+    }
+
+    /// Completes `line` with the cursor at its end.
+    fn complete(line: &str) -> Vec<String> {
+        block_on(completer().complete(line, line.len()))
+            .into_iter()
+            .map(|suggestion| suggestion.replacement)
+            .collect()
+    }
+
+    #[test]
+    fn command_names_include_executables() {
+        assert_eq!(complete("ca"), ["cargo", "cat"]);
+        assert_eq!(complete("ls"), ["ls", "lsblk"]);
+        assert_eq!(complete("pwd | m"), ["mkdir"]);
+    }
+
+    #[test]
+    fn suggestions_replace_the_word_before_the_cursor() {
+        let suggestions = block_on(completer().complete("ls | ca foo", 7));
+        assert_eq!(suggestions.len(), 2);
+        assert!(suggestions
+            .iter()
+            .all(|suggestion| suggestion.span == Span::new(5, 7)));
+    }
+
+    #[test]
+    fn paths_for_path_positionals() {
+        assert_eq!(complete("cd s"), ["src/", "src/main.rs"]);
+        assert_eq!(complete("ls src/m"), ["src/main.rs"]);
+        assert_eq!(complete("cat C"), ["Cargo.toml"]);
+        assert_eq!(complete("ls > t"), ["target/"]);
+    }
+
+    #[test]
+    fn quotes_are_kept() {
+        assert_eq!(complete("cd \"sr"), ["\"src/", "\"src/main.rs"]);
+    }
+
+    #[test]
+    fn flags() {
+        assert_eq!(complete("ls --a"), ["--all"]);
+        assert_eq!(complete("mkdir -"), ["--parents"]);
+        assert!(complete("ls --x").is_empty());
+        // External commands have no known flags, only paths.
+        assert!(complete("cat --").is_empty());
+    }
+}
@@ -1,13 +1,20 @@
 use crate::{
+    cli::history::History,
     commands::CommandRef,
     error::ShellError,
-    evaluate::{call_info::CallInfo, evaluate_args, Value},
-    parser::hir::Call,
+    evaluate::{call_info::CallInfo, evaluate_args, scope::Scope},
+    jobs::{Job, JobTable},
+    parser::{hir::Call, token::SpannedToken},
     shell::Shell,
     signature::Signature,
+    stream::{InputStream, OutputStream},
 };
-use alloc::{string::{String, ToString}, sync::Arc, vec::Vec};
-use core::sync::atomic::AtomicBool;
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, AtomicI32, Ordering};
 use indexmap::IndexMap;
 use spin::RwLock;
 
@@ -24,7 +31,10 @@ impl CommandRegistry {
 
     #[inline]
     pub fn get(&self, name: &str) -> Option<Signature> {
-        self.registry.read().get(name).map(|command| command.signature())
+        self.registry
+            .read()
+            .get(name)
+            .map(|command| command.signature())
     }
 
     #[inline]
@@ -44,7 +54,7 @@ impl CommandRegistry {
     }
 
     #[inline]
-    pub fn insert(&mut self, name: impl Into<String>, command: CommandRef) {
+    pub fn insert(&self, name: impl Into<String>, command: CommandRef) {
         self.registry.write().insert(name.into(), command);
     }
 
@@ -54,12 +64,43 @@ impl CommandRegistry {
     }
 }
 
+/// The command that an alias stands for, as it was typed and as the tokens it was parsed into
+#[derive(Debug, Clone)]
+pub struct Alias {
+    pub text: String,
+    /// The tokens of the command, with spans that point into `text`
+    pub tokens: Vec<SpannedToken>,
+}
+
+/// The aliases defined with `alias`, by name
+pub type Aliases = IndexMap<String, Alias>;
+
 #[derive(Clone)]
 pub struct Context {
     pub registry: CommandRegistry,
+    /// The errors of pipelines in blocks and substitutions that a `;` moved past, for the prompt
+    /// to print
     pub current_errors: Arc<RwLock<Vec<ShellError>>>,
+    /// Interrupts the pipeline this context runs: the one in the foreground, or a background job,
+    /// which gets a context with a flag of its own
     pub ctrl_c: Arc<AtomicBool>,
     pub shell: Arc<dyn Shell>,
+    pub history: Arc<RwLock<History>>,
+    pub scope: Arc<RwLock<Scope>>,
+    /// Whether the output of the internal command being run is read by something, like the next
+    /// stage of its pipeline or a substitution, rather than printed. The blocks the command runs
+    /// capture the output of their external commands when it is. Set by `run_pipeline`.
+    pub capture: bool,
+    /// How many calls of commands defined with `def` the command being run is nested in
+    pub depth: usize,
+    /// The exit status of the last pipeline that ran, read as `$last_exit`
+    pub last_exit: Arc<AtomicI32>,
+    /// The pipelines started in the background with `&`
+    pub jobs: Arc<RwLock<JobTable>>,
+    /// The background job this context runs, which gets the processes it starts
+    pub job: Option<Job>,
+    /// The aliases, which are expanded when a command is classified
+    pub aliases: Arc<RwLock<Aliases>>,
 }
 
 impl Context {
@@ -69,6 +110,36 @@ impl Context {
             current_errors: Arc::new(RwLock::new(Vec::new())),
             ctrl_c: Arc::new(AtomicBool::new(false)),
             shell,
+            history: Arc::new(RwLock::new(History::default())),
+            scope: Arc::new(RwLock::new(Scope::new())),
+            capture: false,
+            depth: 0,
+            last_exit: Arc::new(AtomicI32::new(0)),
+            jobs: Arc::new(RwLock::new(JobTable::default())),
+            job: None,
+            aliases: Arc::new(RwLock::new(Aliases::new())),
+        }
+    }
+
+    /// Records how a pipeline ended as its exit status: 0 when it succeeded, or the
+    /// `ShellError::exit_status` of its error. Returns whether it succeeded.
+    pub fn set_last_exit(&self, error: Option<&ShellError>) -> bool {
+        let status = error.map_or(0, ShellError::exit_status);
+        self.last_exit.store(status, Ordering::Release);
+
+        status == 0
+    }
+
+    /// A copy of the context for running a block in, with a variable frame of its own on top of the
+    /// variables in scope here. The frame lasts as long as the copy, so the streams that the block
+    /// outputs still see its variables after the block has returned them.
+    pub fn with_frame(&self) -> Context {
+        let mut scope = self.scope.read().clone();
+        scope.enter();
+
+        Context {
+            scope: Arc::new(RwLock::new(scope)),
+            ..self.clone()
         }
     }
 
@@ -86,22 +157,18 @@ impl Context {
         self.registry.expect_command(name)
     }
 
-    pub fn run_command(
+    pub async fn run_command(
         &mut self,
         command: CommandRef,
         args: Call,
         source: &str,
-        input: Option<Vec<Value>>,
-    ) -> Result<Option<Vec<Value>>, ShellError> {
+        input: InputStream,
+    ) -> Result<OutputStream, ShellError> {
+        let name = args.head.span;
         let call_info = CallInfo {
-            args: evaluate_args(args, command.clone(), &self.registry, source)?,
+            args: evaluate_args(args, command.clone(), self, source).await?,
+            name,
         };
-        command.run(
-            call_info,
-            input,
-            self.ctrl_c.clone(),
-            self.shell.clone(),
-            &self.registry,
-        )
+        command.run(call_info, input, self).await
     }
 }
@@ -1,7 +1,7 @@
 #![allow(unused)]
 use crate::{
     error::ShellError,
-    evaluate::{CallInfo, Value},
+    evaluate::{value::Record, CallInfo, Value},
 };
 use alloc::{
     string::{String, ToString},
@@ -45,7 +45,15 @@ impl<'de> ConfigDeserializer<'de> {
         let value: Option<Value> = if name == "rest" {
             let positional = self.call.args.slice_from(self.position);
             self.position += positional.len();
-            Some(Value::List(positional))
+            // A list literal passed to a rest parameter supplies its items one by one.
+            let items = positional
+                .into_iter()
+                .flat_map(|value| match value {
+                    Value::List(items) => items,
+                    value => vec![value],
+                })
+                .collect();
+            Some(Value::List(items))
         } else if self.call.args.has(name) {
             self.call.args.get(name).cloned()
         } else {
@@ -62,18 +70,18 @@ impl<'de> ConfigDeserializer<'de> {
         Ok(())
     }
 
-    pub fn top(&mut self) -> &DeserializerItem {
+    pub fn top(&mut self) -> &DeserializerItem<'de> {
         let value = self.stack.last();
         value.expect("Can't get top element of an empty stack")
     }
 
-    pub fn pop(&mut self) -> DeserializerItem {
+    pub fn pop(&mut self) -> DeserializerItem<'de> {
         let value = self.stack.pop();
         value.expect("Can't pop an empty stack")
     }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut ConfigDeserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut ConfigDeserializer<'de> {
     type Error = ShellError;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -126,7 +134,13 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut ConfigDeserializer<'de> {
             Value::Number(i) => i.to_i64(),
             Value::String(s) => s.parse().ok(),
             Value::Boolean(b) => Some(b as i64),
-            Value::List(_) | Value::Nothing | Value::Path(_) | Value::Pattern(_) => None,
+            Value::List(_)
+            | Value::Record(_)
+            | Value::Table(_)
+            | Value::Nothing
+            | Value::Path(_)
+            | Value::Pattern(_)
+            | Value::Block(_) => None,
         }
         .ok_or_else(|| ShellError::runtime_error("expected Integer"))?;
         visitor.visit_i64(val)
@@ -177,7 +191,13 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut ConfigDeserializer<'de> {
             Value::Number(i) => i.to_f64(),
             Value::String(s) => s.parse().ok(),
             Value::Boolean(b) => Some(b as i8 as f64),
-            Value::List(_) | Value::Nothing | Value::Path(_) | Value::Pattern(_) => None,
+            Value::List(_)
+            | Value::Record(_)
+            | Value::Table(_)
+            | Value::Nothing
+            | Value::Path(_)
+            | Value::Pattern(_)
+            | Value::Block(_) => None,
         }
         .ok_or_else(|| ShellError::runtime_error("expected Number"))?;
         visitor.visit_f64(val)
@@ -269,28 +289,33 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut ConfigDeserializer<'de> {
         unimplemented!()
     }
 
-    fn deserialize_seq<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
         let value = self.pop();
         match value.val {
             Value::List(items) => {
-                let de = SeqDeserializer::new(&mut self, items.into_iter());
+                let de = SeqDeserializer::new(self, items.into_iter());
                 visitor.visit_seq(de)
             }
+            Value::Table(rows) => {
+                let de = SeqDeserializer::new(self, rows.into_iter().map(Value::Record));
+                visitor.visit_seq(de)
+            }
+            Value::Nothing => visitor.visit_seq(SeqDeserializer::new(self, core::iter::empty())),
             _ => Err(ShellError::runtime_error("expected Vec")),
         }
     }
 
-    fn deserialize_tuple<V>(mut self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
         let value = self.pop();
         match value.val {
             Value::List(items) => {
-                let de = SeqDeserializer::new(&mut self, items.into_iter());
+                let de = SeqDeserializer::new(self, items.into_iter());
                 visitor.visit_seq(de)
             }
             _ => Err(ShellError::runtime_error("expected Tuple")),
@@ -313,11 +338,16 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut ConfigDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        let value = self.pop();
+        match value.val {
+            Value::Record(record) => visitor.visit_map(MapDeserializer::new(self, record)),
+            Value::Nothing => visitor.visit_map(MapDeserializer::new(self, Record::new())),
+            _ => Err(ShellError::runtime_error("expected Record")),
+        }
     }
 
     fn deserialize_struct<V>(
-        mut self,
+        self,
         name: &'static str,
         fields: &'static [&'static str],
         visitor: V,
@@ -325,7 +355,13 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut ConfigDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_seq(StructDeserializer::new(&mut self, fields))
+        // Only the arguments of the call itself are read positionally, a nested struct comes from
+        // a record value.
+        if self.stack.is_empty() {
+            visitor.visit_seq(StructDeserializer::new(self, fields))
+        } else {
+            self.deserialize_map(visitor)
+        }
     }
 
     fn deserialize_enum<V>(
@@ -344,7 +380,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut ConfigDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_string(visitor)
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -392,6 +428,57 @@ impl<'a, 'de: 'a, I: Iterator<Item = Value>> de::SeqAccess<'de> for SeqDeseriali
     }
 }
 
+struct MapDeserializer<'a, 'de: 'a> {
+    de: &'a mut ConfigDeserializer<'de>,
+    entries: indexmap::map::IntoIter<String, Value>,
+    value: Option<Value>,
+}
+
+impl<'a, 'de: 'a> MapDeserializer<'a, 'de> {
+    fn new(de: &'a mut ConfigDeserializer<'de>, record: Record) -> Self {
+        MapDeserializer {
+            de,
+            entries: record.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'a, 'de: 'a> de::MapAccess<'de> for MapDeserializer<'a, 'de> {
+    type Error = ShellError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let (key, value) = if let Some(entry) = self.entries.next() {
+            entry
+        } else {
+            return Ok(None);
+        };
+
+        self.value = Some(value);
+        self.de.push_val(Value::String(key));
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| ShellError::runtime_error("expected a record value"))?;
+        self.de.push_val(value);
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
 struct StructDeserializer<'a, 'de: 'a> {
     de: &'a mut ConfigDeserializer<'de>,
     fields: &'static [&'static str],
@@ -1,5 +1,8 @@
 use crate::parser::span::Span;
-use alloc::{boxed::Box, string::{String, ToString}};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+};
 use core::fmt;
 use serde::{Deserialize, Serialize};
 
@@ -17,17 +20,96 @@ impl ShellError {
     ) -> ShellError {
         let reason = Some(String::from("parse error"));
         match error {
-            nom::Err::Incomplete(_) => {
-                ProximateShellError::ParseError(Span::unknown(), reason).start()
-            }
+            nom::Err::Incomplete(_) => ProximateShellError::IncompleteInput(
+                Span::unknown(),
+                String::from("incomplete input"),
+            )
+            .start(),
             nom::Err::Failure(span) | nom::Err::Error(span) => {
                 ProximateShellError::ParseError(Span::from(span.input), reason).start()
             }
         }
     }
+
+    /// Whether the error only says that the input stopped too early, so that more input could
+    /// complete it.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self.error, ProximateShellError::IncompleteInput(..))
+    }
+
     pub fn runtime_error(reason: impl Into<String>) -> ShellError {
         ProximateShellError::RuntimeError(reason.into()).start()
     }
+
+    pub fn labeled_error(reason: impl Into<String>, span: Span) -> ShellError {
+        ProximateShellError::LabeledError(span, reason.into()).start()
+    }
+
+    /// Points an error that doesn't know where it came from at `span`.
+    pub fn spanned(self, span: Span) -> ShellError {
+        match self.error {
+            ProximateShellError::RuntimeError(reason) => ShellError {
+                error: ProximateShellError::LabeledError(span, reason),
+                cause: self.cause,
+            },
+            _ => self,
+        }
+    }
+
+    /// Moves the span the error points at `offset` bytes towards the start, eg to point into one
+    /// line of a longer source.
+    pub fn shift_span(mut self, offset: usize) -> ShellError {
+        match &mut self.error {
+            ProximateShellError::ParseError(span, _)
+            | ProximateShellError::IncompleteInput(span, _)
+            | ProximateShellError::LabeledError(span, _)
+            | ProximateShellError::ExitStatus(span, _) => {
+                *span = Span::new(
+                    span.start().saturating_sub(offset),
+                    span.end().saturating_sub(offset),
+                );
+            }
+            ProximateShellError::RuntimeError(_) => {}
+        }
+        self
+    }
+
+    /// Points an error that points inside `from` at `to` instead, eg at the alias that a command
+    /// was expanded from.
+    pub fn relocate(mut self, from: Span, to: Span) -> ShellError {
+        match &mut self.error {
+            ProximateShellError::ParseError(span, _)
+            | ProximateShellError::IncompleteInput(span, _)
+            | ProximateShellError::LabeledError(span, _)
+            | ProximateShellError::ExitStatus(span, _) => {
+                if span.start() >= from.start() && span.end() <= from.end() {
+                    *span = to;
+                }
+            }
+            ProximateShellError::RuntimeError(_) => {}
+        }
+        self
+    }
+
+    /// The exit status of a pipeline that failed with this error: the status of an external
+    /// command that failed, or 1 for any other error.
+    pub fn exit_status(&self) -> i32 {
+        match self.error {
+            ProximateShellError::ExitStatus(_, status) => status,
+            _ => 1,
+        }
+    }
+
+    /// Returns the span of the source this error points at, if any.
+    pub fn span(&self) -> Option<Span> {
+        match self.error {
+            ProximateShellError::ParseError(span, _)
+            | ProximateShellError::IncompleteInput(span, _)
+            | ProximateShellError::LabeledError(span, _)
+            | ProximateShellError::ExitStatus(span, _) => Some(span),
+            ProximateShellError::RuntimeError(_) => None,
+        }
+    }
 }
 
 impl fmt::Display for ShellError {
@@ -55,6 +137,10 @@ impl From<std::io::Error> for ShellError {
 #[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Hash, Deserialize, Serialize)]
 pub enum ProximateShellError {
     ParseError(Span, Option<String>),
+    /// The input ended before what was started at the span, eg an unclosed quote
+    IncompleteInput(Span, String),
+    LabeledError(Span, String),
+    ExitStatus(Span, i32),
     RuntimeError(String),
 }
 
@@ -76,12 +162,29 @@ impl fmt::Display for ProximateShellError {
                 ProximateShellError::ParseError(span, reason) => {
                     let reason = reason.clone().unwrap_or_default();
                     format!(
-                        "{}{}{}",
+                        "{}{} {}",
                         " ".repeat(span.start()),
                         "^".repeat(span.len()),
                         reason
                     )
                 }
+                ProximateShellError::IncompleteInput(span, reason)
+                | ProximateShellError::LabeledError(span, reason) => {
+                    format!(
+                        "{}{} {}",
+                        " ".repeat(span.start()),
+                        "^".repeat(span.len()),
+                        reason
+                    )
+                }
+                ProximateShellError::ExitStatus(span, status) => {
+                    format!(
+                        "{}{} exited with status {}",
+                        " ".repeat(span.start()),
+                        "^".repeat(span.len()),
+                        status
+                    )
+                }
                 ProximateShellError::RuntimeError(reason) => {
                     reason.clone()
                 }
@@ -89,3 +192,49 @@ impl fmt::Display for ProximateShellError {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_span_moves_the_span_towards_the_start() {
+        let err = ShellError::labeled_error("bad", Span::new(10, 14)).shift_span(4);
+        assert_eq!(err.span(), Some(Span::new(6, 10)));
+    }
+
+    #[test]
+    fn shift_span_stops_at_the_start() {
+        let err = ShellError::labeled_error("bad", Span::new(2, 8)).shift_span(4);
+        assert_eq!(err.span(), Some(Span::new(0, 4)));
+    }
+
+    #[test]
+    fn shift_span_keeps_errors_without_a_span() {
+        let err = ShellError::runtime_error("bad");
+        assert_eq!(err.clone().shift_span(4), err);
+    }
+
+    #[test]
+    fn relocate_moves_errors_inside_the_span() {
+        let from = Span::new(20, 30);
+        let to = Span::new(0, 3);
+        let err = ShellError::labeled_error("bad", Span::new(22, 25)).relocate(from, to);
+        assert_eq!(err.span(), Some(to));
+        let err = ProximateShellError::ExitStatus(from, 1)
+            .start()
+            .relocate(from, to);
+        assert_eq!(err.span(), Some(to));
+    }
+
+    #[test]
+    fn relocate_keeps_errors_outside_the_span() {
+        let from = Span::new(20, 30);
+        let to = Span::new(0, 3);
+        let err = ShellError::labeled_error("bad", Span::new(5, 8)).relocate(from, to);
+        assert_eq!(err.span(), Some(Span::new(5, 8)));
+        // Only partly inside
+        let err = ShellError::labeled_error("bad", Span::new(18, 22)).relocate(from, to);
+        assert_eq!(err.span(), Some(Span::new(18, 22)));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::evaluate::value::Value;
+use crate::{evaluate::value::Value, parser::span::Span};
 use alloc::{string::String, vec::Vec};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
@@ -8,7 +8,8 @@ use serde::{Deserialize, Serialize};
 pub struct CallInfo {
     /// The arguments associated with this call
     pub args: EvaluatedArgs,
-    //    pub name: Span,
+    /// The name of the command as the call spells it
+    pub name: Span,
 }
 
 /// The set of positional and named arguments, after their values have been evaluated.
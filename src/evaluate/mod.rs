@@ -1,68 +1,1044 @@
 use crate::{
-    commands::CommandRef,
-    context::CommandRegistry,
-    error::ShellError,
+    cli::{render, start_job},
+    commands::{run_pipeline, unhandled_failure, CommandRef},
+    context::Context,
+    error::{ProximateShellError, ShellError},
     evaluate::call_info::EvaluatedArgs,
     parser::{
+        classify_pipeline_with_aliases,
+        command::classified::external::ExternalArgs,
         hir,
+        span::{HasSpan, Span, SpannedItem},
+        syntax_shape::SyntaxShape,
         token::{SpannedToken, Token},
+        unescape, unquote,
     },
+    signature::NamedType,
+    stream::{block_on, Failure, OutputStream},
+    BoxFuture,
 };
-use alloc::{string::String, vec::Vec};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{str::FromStr, sync::atomic::Ordering};
 use indexmap::IndexMap;
+use num_bigint::BigInt;
+use ordered_float::OrderedFloat;
 
 pub(crate) use call_info::CallInfo;
-pub(crate) use value::Value;
+pub(crate) use value::{Block, Value};
 
 pub mod call_info;
+pub mod scope;
 pub mod value;
 
-fn evaluate_expr(spanned: &SpannedToken, source: &str) -> Result<Value, ShellError> {
-    let token = &spanned.item;
-    match token {
-        Token::String(s) => Ok(Value::String(s.string(source))),
-        Token::Bare | Token::GlobPattern | Token::ExternalWord => {
-            Ok(Value::String(spanned.span.string(source)))
+// Boxed, as a command substitution evaluates the arguments of the commands inside it in turn.
+fn evaluate_expr<'a>(
+    spanned: &'a SpannedToken,
+    context: &'a Context,
+    source: &'a str,
+) -> BoxFuture<'a, Result<Value, ShellError>> {
+    Box::pin(async move {
+        let token = &spanned.item;
+        match token {
+            Token::String(s) => Ok(Value::String(unquote(spanned, *s, source)?)),
+            Token::Bare | Token::GlobPattern | Token::ExternalWord => {
+                Ok(Value::String(spanned.span.string(source)))
+            }
+            Token::Variable(name) => evaluate_variable(*name, spanned.span, context, source),
+            Token::Invocation(tokens) => {
+                evaluate_invocation(tokens, spanned.span, context, source).await
+            }
+            Token::Interpolation(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    match &part.item {
+                        Token::String(s) => text.push_str(&unescape(*s, source)?),
+                        _ => {
+                            text.push_str(&evaluate_expr(part, context, source).await?.to_string())
+                        }
+                    }
+                }
+                Ok(Value::String(text))
+            }
+            Token::List(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(evaluate_expr(item, context, source).await?);
+                }
+                Ok(Value::List(values))
+            }
+            Token::Record(entries) => {
+                let mut record = IndexMap::with_capacity(entries.len());
+                for (key, value) in entries {
+                    let value = evaluate_expr(value, context, source).await?;
+                    record.insert(record_key(key, source)?, value);
+                }
+                Ok(Value::Record(record))
+            }
+            Token::Block(tokens) => Ok(Value::Block(Block {
+                tokens: tokens.clone(),
+                span: spanned.span,
+                source: source.into(),
+            })),
+            Token::Flag(_)
+            | Token::ShortFlag(_)
+            | Token::FlagWithValue(..)
+            | Token::Whitespace
+            | Token::Separator
+            | Token::Pipe
+            | Token::And
+            | Token::Or
+            | Token::Background
+            | Token::Redirect(..) => Err(ShellError::runtime_error(format!(
+                "unexpected {}",
+                token.desc()
+            ))),
+        }
+    })
+}
+
+/// Evaluates an argument declared with the given shape. Words are read as the shape asks for,
+/// while quoted strings, variables and substitutions must already hold a value that fits it.
+fn evaluate_typed<'a>(
+    spanned: &'a SpannedToken,
+    shape: SyntaxShape,
+    context: &'a Context,
+    source: &'a str,
+) -> BoxFuture<'a, Result<Value, ShellError>> {
+    Box::pin(async move {
+        match &spanned.item {
+            Token::Bare | Token::GlobPattern | Token::ExternalWord => {
+                evaluate_word(spanned.span.slice(source), shape, spanned.span, context)
+            }
+            Token::List(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(evaluate_typed(item, shape, context, source).await?);
+                }
+                Ok(Value::List(values))
+            }
+            // `{}` reads as an empty record, but is just as good as an empty block.
+            Token::Record(entries) if entries.is_empty() && matches!(shape, SyntaxShape::Block) => {
+                Ok(Value::Block(Block {
+                    tokens: vec![],
+                    span: spanned.span,
+                    source: source.into(),
+                }))
+            }
+            // The fields of a record have no declared shape, their words are read as for `Any`.
+            Token::Record(entries) => {
+                let mut record = IndexMap::with_capacity(entries.len());
+                for (key, value) in entries {
+                    let value = evaluate_typed(value, SyntaxShape::Any, context, source).await?;
+                    record.insert(record_key(key, source)?, value);
+                }
+                coerce(Value::Record(record), shape, spanned.span)
+            }
+            _ => {
+                let value = evaluate_expr(spanned, context, source).await?;
+                coerce(value, shape, spanned.span)
+            }
+        }
+    })
+}
+
+fn record_key(key: &SpannedToken, source: &str) -> Result<String, ShellError> {
+    match &key.item {
+        Token::String(s) => unquote(key, *s, source),
+        _ => Ok(key.span.string(source)),
+    }
+}
+
+fn evaluate_word(
+    word: &str,
+    shape: SyntaxShape,
+    span: Span,
+    context: &Context,
+) -> Result<Value, ShellError> {
+    match shape {
+        SyntaxShape::Any => Ok(match word {
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            _ => parse_number(word).unwrap_or_else(|| Value::String(word.into())),
+        }),
+        SyntaxShape::Path => Ok(Value::Path(expand_tilde(word, context))),
+        SyntaxShape::Pattern => Ok(Value::Pattern(expand_tilde(word, context))),
+        _ => coerce(Value::String(word.into()), shape, span),
+    }
+}
+
+/// Checks that `value` fits `shape`, converting text into the number, path or pattern it spells.
+fn coerce(value: Value, shape: SyntaxShape, span: Span) -> Result<Value, ShellError> {
+    let value = match (shape, value) {
+        (SyntaxShape::Any, value) => value,
+        (_, Value::List(items)) => Value::List(
+            items
+                .into_iter()
+                .map(|item| coerce(item, shape, span))
+                .collect::<Result<_, _>>()?,
+        ),
+        (SyntaxShape::String, Value::String(s))
+        | (SyntaxShape::String, Value::Path(s))
+        | (SyntaxShape::String, Value::Pattern(s)) => Value::String(s),
+        (SyntaxShape::String, value @ Value::Int(_))
+        | (SyntaxShape::String, value @ Value::Number(_)) => Value::String(value.to_string()),
+        (SyntaxShape::Int, value @ Value::Int(_)) => value,
+        (SyntaxShape::Number, value @ Value::Int(_))
+        | (SyntaxShape::Number, value @ Value::Number(_)) => value,
+        (SyntaxShape::Int, Value::String(s)) => match BigInt::from_str(&s) {
+            Ok(int) => Value::Int(int),
+            Err(_) => return Err(mismatch(shape, "string", span)),
+        },
+        (SyntaxShape::Number, Value::String(s)) => {
+            parse_number(&s).ok_or_else(|| mismatch(shape, "string", span))?
         }
-        Token::Flag(_) | Token::Whitespace | Token::Separator => Err(ShellError::runtime_error(
-            format!("unexpected {}", token.desc()),
-        )),
+        (SyntaxShape::Path, Value::String(s))
+        | (SyntaxShape::Path, Value::Path(s))
+        | (SyntaxShape::Path, Value::Pattern(s)) => Value::Path(s),
+        (SyntaxShape::Pattern, Value::String(s))
+        | (SyntaxShape::Pattern, Value::Path(s))
+        | (SyntaxShape::Pattern, Value::Pattern(s)) => Value::Pattern(s),
+        (SyntaxShape::Block, value @ Value::Block(_)) => value,
+        (_, value) => return Err(mismatch(shape, value.type_name(), span)),
+    };
+
+    Ok(value)
+}
+
+fn mismatch(shape: SyntaxShape, found: &str, span: Span) -> ShellError {
+    ShellError::labeled_error(format!("expected {}, found {}", shape, found), span)
+}
+
+/// Reads an integer, or a decimal number written with digits, an optional sign, point and
+/// exponent.
+fn parse_number(word: &str) -> Option<Value> {
+    if let Ok(int) = BigInt::from_str(word) {
+        return Some(Value::Int(int));
+    }
+    if !word.contains(|c: char| c.is_ascii_digit())
+        || !word
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-.eE".contains(c))
+    {
+        return None;
+    }
+
+    f64::from_str(word)
+        .ok()
+        .map(|number| Value::Number(OrderedFloat(number)))
+}
+
+/// Replaces a leading `~` with the home directory of the shell.
+fn expand_tilde(word: &str, context: &Context) -> String {
+    match (word.strip_prefix('~'), context.shell.homedir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home, rest)
+        }
+        _ => word.into(),
+    }
+}
+
+/// Runs the pipelines of a `(...)` substitution and collects what they output into one value:
+/// nothing, the single value that came out, or a list of all of them. A lone value that can't
+/// name a command, like `($name)`, is evaluated as it is.
+///
+/// The substitution fails when its last pipeline that ran failed. The failures that a `;` moved
+/// past are left in `Context::current_errors`.
+async fn evaluate_invocation(
+    tokens: &[SpannedToken],
+    span: Span,
+    context: &Context,
+    source: &str,
+) -> Result<Value, ShellError> {
+    let mut words = tokens
+        .iter()
+        .filter(|token| !matches!(token.item, Token::Whitespace | Token::Separator));
+    if let (Some(word), None) = (words.next(), words.next()) {
+        if let Token::Variable(_)
+        | Token::Invocation(_)
+        | Token::Interpolation(_)
+        | Token::List(_)
+        | Token::Record(_) = word.item
+        {
+            return evaluate_expr(word, context, source).await;
+        }
+    }
+
+    let mut source = String::from(source);
+    let tokens = tokens.to_vec().spanned(span);
+    let pipelines = classify_pipeline_with_aliases(
+        tokens,
+        &context.registry,
+        &context.aliases.read(),
+        &mut source,
+    )?;
+    let mut context = context.clone();
+    let mut values = vec![];
+    let mut failure = None;
+    for pipeline in pipelines {
+        if let Some(err) = unhandled_failure(&mut failure, pipeline.condition) {
+            context.current_errors.write().push(err);
+        }
+        if !pipeline.condition.runs_after(failure.is_none()) {
+            continue;
+        }
+        // Its output is what the substitution is waiting for.
+        if pipeline.background {
+            return Err(ShellError::labeled_error(
+                "a substitution can't start a background job",
+                pipeline.span(),
+            ));
+        }
+        let result = run_pipeline(pipeline, &mut context, &source, true)
+            .await
+            .and_then(|output| {
+                let failures = output.failures();
+                values.extend(output.take_while(|_| !context.ctrl_c.load(Ordering::Acquire)));
+                failures.check()
+            });
+        context.set_last_exit(result.as_ref().err());
+        failure = result.err();
+    }
+    if let Some(err) = failure {
+        return Err(err);
+    }
+
+    Ok(match values.len() {
+        0 => Value::Nothing,
+        1 => values.remove(0),
+        _ => Value::List(values),
+    })
+}
+
+/// Runs the pipelines of `block` in a variable frame of their own, which lives on in the stream the
+/// block outputs.
+///
+/// They run as they would at the prompt: `&&` and `||` depend on how the last pipeline went, `;`
+/// moves on either way, and what all but the last pipeline output is printed once it is done. The
+/// output of the last one is the output of the block, which fails when that pipeline failed. Its
+/// external commands are captured when `Context::capture` says the block's output is read. The
+/// failures that a `;` moved past are left in `Context::current_errors`.
+pub(crate) async fn run_block(
+    block: &Block,
+    context: &Context,
+) -> Result<OutputStream, ShellError> {
+    let mut source = block.source.clone();
+    let tokens = block.tokens.clone().spanned(block.span);
+    let pipelines = classify_pipeline_with_aliases(
+        tokens,
+        &context.registry,
+        &context.aliases.read(),
+        &mut source,
+    )?;
+    let source = source.as_str();
+
+    let mut context = context.with_frame();
+    let capture = context.capture;
+    let last = pipelines.len().saturating_sub(1);
+    let mut output = OutputStream::empty();
+    let mut failure = None;
+    for (index, pipeline) in pipelines.into_iter().enumerate() {
+        // The output of the pipeline before has to run out to know whether it failed.
+        if let Err(err) = render(core::mem::take(&mut output), &context) {
+            context.set_last_exit(Some(&err));
+            failure = Some(err);
+        }
+        if let Some(err) = unhandled_failure(&mut failure, pipeline.condition) {
+            context.current_errors.write().push(err);
+        }
+        if !pipeline.condition.runs_after(failure.is_none()) {
+            continue;
+        }
+        let result = if pipeline.background {
+            start_job(pipeline, &context, source).map(|()| OutputStream::empty())
+        } else {
+            run_pipeline(pipeline, &mut context, source, capture && index == last).await
+        };
+        context.set_last_exit(result.as_ref().err());
+        match result {
+            Ok(stream) => {
+                failure = None;
+                output = stream;
+            }
+            Err(err) => failure = Some(err),
+        }
+    }
+
+    match failure {
+        Some(err) => Err(err),
+        None => Ok(output),
+    }
+}
+
+/// Decides the condition of an `if` or `while`. A block is run, and is true when it succeeds
+/// without outputting anything or outputs a single value that is true. Any other value is true as
+/// `Value::is_true` decides.
+///
+/// A block that fails with an exit status is false, any other error is passed on.
+pub(crate) async fn is_true(condition: &Value, context: &Context) -> Result<bool, ShellError> {
+    let block = match condition {
+        Value::Block(block) => block,
+        value => return Ok(value.is_true()),
+    };
+    // Its output is read to decide, so its external commands are captured.
+    let context = &Context {
+        capture: true,
+        ..context.clone()
+    };
+    let result = run_block(block, context).await.and_then(|output| {
+        let failures = output.failures();
+        let values: Vec<Value> = output
+            .take_while(|_| !context.ctrl_c.load(Ordering::Acquire))
+            .collect();
+        failures.check().map(|()| values)
+    });
+    match result {
+        Ok(values) => Ok(match values.as_slice() {
+            [value] => value.is_true(),
+            _ => true,
+        }),
+        Err(err) if matches!(err.error, ProximateShellError::ExitStatus(..)) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// The variables that an iteration of a loop sets, by name
+pub(crate) type Bindings = Vec<(String, Value)>;
+
+/// Runs `body` as the body of a loop, once for every set of variables that `next` returns until it
+/// returns `None`.
+///
+/// Each iteration runs in a frame of its own once the output of the one before has run out, so a
+/// long or endless loop streams its output and stops at ctrl-c. A failed iteration, or an error
+/// from `next`, ends the loop as the failure of its stream.
+pub(crate) fn run_loop<F>(body: &Block, context: &Context, next: F) -> OutputStream
+where
+    F: FnMut(&Context) -> Result<Option<Bindings>, ShellError> + Send + 'static,
+{
+    let failure = Failure::default();
+    let iterations = Loop {
+        body: body.clone(),
+        context: context.clone(),
+        next,
+        output: OutputStream::empty(),
+        failure: failure.clone(),
+        done: false,
+    };
+
+    OutputStream::new(iterations).with_failure(failure)
+}
+
+struct Loop<F> {
+    body: Block,
+    context: Context,
+    next: F,
+    /// The output of the current iteration
+    output: OutputStream,
+    failure: Failure,
+    done: bool,
+}
+
+impl<F> Loop<F>
+where
+    F: FnMut(&Context) -> Result<Option<Bindings>, ShellError>,
+{
+    /// Runs the next iteration, returning its output.
+    fn iterate(&mut self) -> Result<Option<OutputStream>, ShellError> {
+        if self.context.ctrl_c.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        let bindings = match (self.next)(&self.context)? {
+            Some(bindings) => bindings,
+            None => return Ok(None),
+        };
+        // The variables only live for one iteration.
+        let context = self.context.with_frame();
+        for (name, value) in bindings {
+            context.scope.write().set(name, value);
+        }
+
+        block_on(run_block(&self.body, &context)).map(Some)
+    }
+}
+
+impl<F> Iterator for Loop<F>
+where
+    F: FnMut(&Context) -> Result<Option<Bindings>, ShellError>,
+{
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        while !self.done {
+            if let Some(value) = self.output.next() {
+                return Some(value);
+            }
+            match self.output.failures().check().and_then(|()| self.iterate()) {
+                Ok(Some(output)) => self.output = output,
+                Ok(None) => self.done = true,
+                Err(err) => {
+                    self.failure.set(err);
+                    self.done = true;
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Looks up `$name.member...`, where `name` is a variable in scope, `env` for the shell's
+/// environment or `last_exit` for the status of the last pipeline, and each member picks a column
+/// out of the record before it.
+fn evaluate_variable(
+    name: Span,
+    span: Span,
+    context: &Context,
+    source: &str,
+) -> Result<Value, ShellError> {
+    let mut start = name.start();
+    let mut members = name.slice(source).split('.').map(|member| {
+        let member_span = Span::new(start, start + member.len());
+        start += member.len() + 1;
+        (member, member_span)
+    });
+    let (head, head_span) = members.next().expect("split returns at least one item");
+    let head_span = span.until(head_span);
+
+    let mut value = if head == "last_exit" {
+        Value::Int(BigInt::from(context.last_exit.load(Ordering::Acquire)))
+    } else if head == "env" {
+        let (var, var_span) = members.next().ok_or_else(|| {
+            ShellError::labeled_error("expected a variable name, eg $env.HOME", head_span)
+        })?;
+        context
+            .shell
+            .env_var(var)
+            .map(Value::String)
+            .ok_or_else(|| ShellError::labeled_error(format!("{} is not set", var), var_span))?
+    } else {
+        context
+            .scope
+            .read()
+            .get(head)
+            .cloned()
+            .ok_or_else(|| ShellError::labeled_error("undefined variable", head_span))?
+    };
+    for (member, member_span) in members {
+        value = match value {
+            Value::Record(mut record) => record.swap_remove(member).ok_or_else(|| {
+                ShellError::labeled_error(format!("no column named {}", member), member_span)
+            })?,
+            _ => {
+                return Err(ShellError::labeled_error(
+                    "only records have columns",
+                    member_span,
+                ))
+            }
+        };
+    }
+
+    Ok(value)
+}
+
+/// Evaluates the file named by a redirection, which is read like a `SyntaxShape::Path` argument.
+pub(crate) async fn evaluate_path(
+    target: &SpannedToken,
+    context: &Context,
+    source: &str,
+) -> Result<String, ShellError> {
+    match evaluate_typed(target, SyntaxShape::Path, context, source).await? {
+        Value::Path(path) => Ok(path),
+        value => Err(mismatch(SyntaxShape::Path, value.type_name(), target.span)),
+    }
+}
+
+/// Evaluates the arguments of an external command into the text passed to the program.
+pub(crate) async fn evaluate_external_args(
+    args: &ExternalArgs,
+    context: &Context,
+    source: &str,
+) -> Result<Vec<String>, ShellError> {
+    let mut list = Vec::with_capacity(args.tokens.len());
+    for token in &args.tokens {
+        list.push(match &token.item {
+            Token::Flag(_) | Token::ShortFlag(_) => token.span.string(source),
+            Token::FlagWithValue(name, value) => format!(
+                "--{}={}",
+                name.slice(source),
+                evaluate_expr(value, context, source).await?
+            ),
+            _ => evaluate_expr(token, context, source).await?.to_string(),
+        });
     }
+
+    Ok(list)
 }
 
-pub(crate) fn evaluate_args(
+pub(crate) async fn evaluate_args(
     call: hir::Call,
-    _command: CommandRef,
-    _registry: &CommandRegistry,
+    command: CommandRef,
+    context: &Context,
     source: &str,
 ) -> Result<EvaluatedArgs, ShellError> {
-    let positional: Result<Option<Vec<_>>, _> = call
-        .positional
-        .as_ref()
-        .map(|p| p.iter().map(|s| evaluate_expr(s, source)).collect())
-        .transpose();
-    let positional = positional?;
-    let named: Result<Option<IndexMap<String, Value>>, ShellError> = call
-        .named
-        .as_ref()
-        .map(|n| {
-            let mut results = IndexMap::new();
-            for (name, value) in n.named.iter() {
-                match value {
-                    hir::NamedValue::PresentSwitch(_) => {
-                        results.insert(name.clone(), Value::Boolean(true));
-                    }
-                    hir::NamedValue::Value(ref expr) => {
-                        results.insert(name.clone(), evaluate_expr(expr, source)?);
-                    }
-                    _ => {}
+    let signature = command.signature();
+    let positional = match &call.positional {
+        Some(positional) => {
+            let mut values = Vec::with_capacity(positional.len());
+            for (index, expr) in positional.iter().enumerate() {
+                let shape = signature
+                    .positional_shape(index)
+                    .unwrap_or(SyntaxShape::Any);
+                values.push(evaluate_typed(expr, shape, context, source).await?);
+            }
+            Some(values)
+        }
+        None => None,
+    };
+    let mut named = IndexMap::new();
+    for (name, value) in call.named.iter().flat_map(hir::NamedArguments::iter) {
+        match value {
+            hir::NamedValue::PresentSwitch(_) => {
+                named.insert(name.clone(), Value::Boolean(true));
+            }
+            hir::NamedValue::Value(expr) => {
+                let shape = match signature.named.get(name) {
+                    Some((NamedType::Mandatory(shape, _), _))
+                    | Some((NamedType::Optional(shape, _), _)) => *shape,
+                    _ => SyntaxShape::Any,
                 };
+                let value = evaluate_typed(expr, shape, context, source).await?;
+                named.insert(name.clone(), value);
             }
+            _ => {}
+        };
+    }
+    // Switches that weren't given are still passed, as false, so that the deserializer never
+    // mistakes their fields for positionals.
+    for (name, (kind, _)) in &signature.named {
+        if let NamedType::Switch(_) = kind {
+            named.entry(name.clone()).or_insert(Value::Boolean(false));
+        }
+    }
+    let named = (!named.is_empty()).then_some(named);
+
+    Ok(EvaluatedArgs::new(positional, named))
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::{Def, First, For, If, Let, Pwd, While},
+        evaluate::value::Record,
+        parser::{classify_pipeline, parse},
+        shell::StdShell,
+    };
+    use alloc::sync::Arc;
+
+    fn context() -> Context {
+        let mut context = Context::new(Arc::new(StdShell::new()));
+        context.add_commands(vec![
+            Arc::new(Def),
+            Arc::new(First),
+            Arc::new(For),
+            Arc::new(If),
+            Arc::new(Let),
+            Arc::new(Pwd),
+            Arc::new(While),
+        ]);
+        context
+    }
 
-            Ok(results)
+    fn block(source: &str) -> Value {
+        Value::Block(Block {
+            tokens: parse(source).unwrap().item,
+            span: Span::new(0, source.len()),
+            source: String::from(source),
         })
-        .transpose();
-    let named = named?;
+    }
 
-    Ok(EvaluatedArgs::new(positional, named))
+    /// Runs the pipelines of `source` one after the other in `context`, returning what the last one
+    /// outputs. The first one that fails ends the run.
+    fn run_in(context: &mut Context, source: &str) -> Result<Vec<Value>, ShellError> {
+        let pipelines = classify_pipeline(parse(source)?, &context.registry, source)?;
+        let mut values = vec![];
+        for pipeline in pipelines {
+            let output = block_on(run_pipeline(pipeline, context, source, true))?;
+            let failures = output.failures();
+            values = output.collect();
+            failures.check()?;
+        }
+
+        Ok(values)
+    }
+
+    fn run(source: &str) -> Result<Vec<Value>, ShellError> {
+        run_in(&mut context(), source)
+    }
+
+    /// Evaluates the first token of `source` as an argument.
+    fn expr(context: &Context, source: &str) -> Result<Value, ShellError> {
+        let tokens = parse(source)?.item;
+        block_on(evaluate_expr(&tokens[0], context, source))
+    }
+
+    /// The text of `source` that an error points at
+    fn error_at<'a>(err: &ShellError, source: &'a str) -> Option<&'a str> {
+        err.span().map(|span| span.slice(source))
+    }
+
+    fn int(n: i64) -> Value {
+        Value::Int(BigInt::from(n))
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(String::from(s))
+    }
+
+    fn is_true_of(condition: &Value) -> Result<bool, ShellError> {
+        block_on(is_true(condition, &context()))
+    }
+
+    #[test]
+    fn values_are_true_as_they_say() {
+        assert!(!is_true_of(&Value::Boolean(false)).unwrap());
+        assert!(!is_true_of(&Value::Nothing).unwrap());
+        assert!(is_true_of(&Value::String(String::from("x"))).unwrap());
+    }
+
+    #[test]
+    fn blocks_are_true_when_they_succeed() {
+        assert!(is_true_of(&block("true")).unwrap());
+        assert!(is_true_of(&block("let x = 1")).unwrap());
+        assert!(is_true_of(&block("pwd")).unwrap());
+        assert!(!is_true_of(&block("false")).unwrap());
+        assert!(!is_true_of(&block("true && false")).unwrap());
+        assert!(is_true_of(&block("false || true")).unwrap());
+    }
+
+    #[test]
+    fn blocks_pass_on_errors_that_are_not_exit_statuses() {
+        let err = is_true_of(&block("pwd | first $undefined")).unwrap_err();
+        assert!(!matches!(err.error, ProximateShellError::ExitStatus(..)));
+    }
+
+    #[test]
+    fn for_runs_the_body_with_each_value() {
+        assert_eq!(run("for x in [1 2 3] { pwd }").unwrap().len(), 3);
+        assert_eq!(run("for n in [0 1 2] { pwd | first $n }").unwrap().len(), 2);
+        assert!(run("for x in [] { pwd }").unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_failed_iteration_ends_the_loop() {
+        assert!(run("for x in [1 2] { pwd extra }").is_err());
+        assert!(run("for n in [1 x 1] { pwd | first $n }").is_err());
+    }
+
+    #[test]
+    fn while_runs_until_the_condition_fails() {
+        assert!(run("while { false } { pwd }").unwrap().is_empty());
+        assert!(run("while { pwd | first $undefined } { pwd }").is_err());
+        assert!(run("while true { pwd }").is_err());
+    }
+
+    #[test]
+    fn loops_stream_their_output() {
+        let values = run("while { true } { pwd } | first 3").unwrap();
+        assert_eq!(values.len(), 3);
+    }
+
+    #[test]
+    fn let_binds_a_variable() {
+        let mut context = context();
+        assert!(run_in(&mut context, "let x = 5; let name = foo")
+            .unwrap()
+            .is_empty());
+        assert_eq!(expr(&context, "$x").unwrap(), int(5));
+        assert_eq!(expr(&context, "$name").unwrap(), string("foo"));
+        // A variable can be set again.
+        run_in(&mut context, "let x = $name").unwrap();
+        assert_eq!(expr(&context, "$x").unwrap(), string("foo"));
+    }
+
+    #[test]
+    fn undefined_variables() {
+        let source = "pwd | first $nope";
+        let err = run(source).unwrap_err();
+        assert_eq!(error_at(&err, source), Some("$nope"));
+    }
+
+    #[test]
+    fn members_of_records() {
+        let context = context();
+        let mut inner = Record::new();
+        inner.insert(String::from("b"), int(1));
+        let mut outer = Record::new();
+        outer.insert(String::from("a"), Value::Record(inner));
+        context.scope.write().set("r", Value::Record(outer));
+        context.scope.write().set("n", int(1));
+
+        assert_eq!(expr(&context, "$r.a.b").unwrap(), int(1));
+        let source = "$r.a.c";
+        let err = expr(&context, source).unwrap_err();
+        assert_eq!(error_at(&err, source), Some("c"));
+        let source = "$n.a";
+        let err = expr(&context, source).unwrap_err();
+        assert_eq!(error_at(&err, source), Some("a"));
+    }
+
+    #[test]
+    fn environment_variables() {
+        let context = context();
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(expr(&context, "$env.PATH").unwrap(), string(&path));
+        let source = "$env.QUEEN_SHELL_SURELY_UNSET";
+        let err = expr(&context, source).unwrap_err();
+        assert_eq!(error_at(&err, source), Some("QUEEN_SHELL_SURELY_UNSET"));
+        assert!(expr(&context, "$env").is_err());
+    }
+
+    #[test]
+    fn last_exit() {
+        let context = context();
+        assert_eq!(expr(&context, "$last_exit").unwrap(), int(0));
+        context.set_last_exit(Some(
+            &ProximateShellError::ExitStatus(Span::unknown(), 3).start(),
+        ));
+        assert_eq!(expr(&context, "$last_exit").unwrap(), int(3));
+    }
+
+    #[test]
+    fn substitutions_output_their_values() {
+        let context = context();
+        let path = Value::Path(context.shell.path());
+        assert_eq!(expr(&context, "(pwd)").unwrap(), path);
+        // Every pipeline adds to the output.
+        assert_eq!(
+            expr(&context, "(pwd; pwd)").unwrap(),
+            Value::List(vec![path.clone(), path.clone()])
+        );
+        assert_eq!(expr(&context, "(pwd | first 0)").unwrap(), Value::Nothing);
+        assert_eq!(
+            expr(&context, "(for x in [1 2] { pwd })").unwrap(),
+            Value::List(vec![path.clone(), path])
+        );
+        // A single expression is its own value.
+        context.scope.write().set("x", int(1));
+        assert_eq!(expr(&context, "($x)").unwrap(), int(1));
+    }
+
+    #[test]
+    fn substitutions_see_the_variables_around_them() {
+        let mut context = context();
+        run_in(&mut context, "let n = 1; let m = (pwd | first $n)").unwrap();
+        assert_eq!(
+            expr(&context, "$m").unwrap(),
+            Value::Path(context.shell.path())
+        );
+    }
+
+    #[test]
+    fn failed_substitutions() {
+        let context = context();
+        assert!(matches!(
+            expr(&context, "(false)").unwrap_err().error,
+            ProximateShellError::ExitStatus(..)
+        ));
+        let source = "(pwd &)";
+        let err = expr(&context, source).unwrap_err();
+        assert_eq!(error_at(&err, source), Some("pwd"));
+    }
+
+    #[test]
+    fn list_and_record_literals() {
+        let mut context = context();
+        run_in(&mut context, "let l = [1, two 2.5 [$last_exit]]").unwrap();
+        assert_eq!(
+            expr(&context, "$l").unwrap(),
+            Value::List(vec![
+                int(1),
+                string("two"),
+                Value::Number(OrderedFloat(2.5)),
+                Value::List(vec![int(0)])
+            ])
+        );
+
+        run_in(
+            &mut context,
+            "let r = {name: x, \"size\": 3, inner: {l: $l}}",
+        )
+        .unwrap();
+        assert_eq!(expr(&context, "$r.name").unwrap(), string("x"));
+        assert_eq!(expr(&context, "$r.size").unwrap(), int(3));
+        assert_eq!(
+            expr(&context, "$r.inner.l").unwrap(),
+            expr(&context, "$l").unwrap()
+        );
+        match expr(&context, "$r").unwrap() {
+            Value::Record(record) => {
+                let keys: Vec<&str> = record.keys().map(String::as_str).collect();
+                assert_eq!(keys, ["name", "size", "inner"]);
+            }
+            value => panic!("expected a record, got {:?}", value),
+        }
+    }
+
+    /// Evaluates the first token of `source` as an argument declared with `shape`.
+    fn typed(source: &str, shape: SyntaxShape) -> Result<Value, ShellError> {
+        let tokens = parse(source)?.item;
+        block_on(evaluate_typed(&tokens[0], shape, &context(), source))
+    }
+
+    #[test]
+    fn words_are_read_as_their_shape() {
+        assert_eq!(typed("12", SyntaxShape::Int).unwrap(), int(12));
+        assert_eq!(typed("12", SyntaxShape::String).unwrap(), string("12"));
+        assert_eq!(
+            typed("1e3", SyntaxShape::Number).unwrap(),
+            Value::Number(OrderedFloat(1000.0))
+        );
+        assert_eq!(typed("3", SyntaxShape::Number).unwrap(), int(3));
+        assert_eq!(
+            typed("*.rs", SyntaxShape::Pattern).unwrap(),
+            Value::Pattern(String::from("*.rs"))
+        );
+        assert_eq!(
+            typed("true", SyntaxShape::Any).unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(typed("-4", SyntaxShape::Any).unwrap(), int(-4));
+        assert_eq!(typed("x1", SyntaxShape::Any).unwrap(), string("x1"));
+    }
+
+    #[test]
+    fn paths_expand_the_home_directory() {
+        let home = context().shell.homedir().unwrap();
+        assert_eq!(
+            typed("~/a", SyntaxShape::Path).unwrap(),
+            Value::Path(format!("{}/a", home))
+        );
+        assert_eq!(typed("~", SyntaxShape::Path).unwrap(), Value::Path(home));
+        assert_eq!(
+            typed("~a", SyntaxShape::Path).unwrap(),
+            Value::Path(String::from("~a"))
+        );
+    }
+
+    #[test]
+    fn values_are_converted_to_their_shape() {
+        assert_eq!(typed("\"12\"", SyntaxShape::Int).unwrap(), int(12));
+        assert_eq!(
+            typed("[1 2]", SyntaxShape::Int).unwrap(),
+            Value::List(vec![int(1), int(2)])
+        );
+        assert!(matches!(
+            typed("{}", SyntaxShape::Block).unwrap(),
+            Value::Block(_)
+        ));
+        assert!(matches!(
+            typed("{ pwd }", SyntaxShape::Block).unwrap(),
+            Value::Block(_)
+        ));
+    }
+
+    #[test]
+    fn values_that_dont_fit_their_shape() {
+        for (source, shape, at) in &[
+            ("x", SyntaxShape::Int, "x"),
+            ("1.5", SyntaxShape::Int, "1.5"),
+            ("[1 x]", SyntaxShape::Int, "x"),
+            ("{a: 1}", SyntaxShape::Int, "{a: 1}"),
+            ("1", SyntaxShape::Block, "1"),
+            ("abc", SyntaxShape::Number, "abc"),
+        ] {
+            let err = typed(source, *shape).unwrap_err();
+            assert_eq!(error_at(&err, source), Some(*at), "{} as {}", source, shape);
+        }
+        let err = typed("x", SyntaxShape::Int).unwrap_err();
+        assert!(err.to_string().contains("found string"));
+    }
+
+    #[test]
+    fn interpolations_splice_in_their_substitutions() {
+        let mut context = context();
+        run_in(&mut context, "let x = 2").unwrap();
+        assert_eq!(
+            expr(&context, r#"$"x is ($x)\t\(ok)""#).unwrap(),
+            string("x is 2\t(ok)")
+        );
+        let source = r#"$"x is ($undefined)""#;
+        let err = expr(&context, source).unwrap_err();
+        assert_eq!(error_at(&err, source), Some("$undefined"));
+    }
+
+    #[test]
+    fn blocks_keep_their_variables_while_their_output_streams() {
+        assert_eq!(
+            run("if true { let x = 1; for y in [a b] { echo $x $y } }").unwrap(),
+            [string("1 a"), string("1 b")]
+        );
+        let mut context = context();
+        run_in(&mut context, "if true { let x = 1 }").unwrap();
+        assert!(expr(&context, "$x").is_err());
+    }
+
+    #[test]
+    fn blocks_capture_their_externals_when_their_output_is_read() {
+        assert_eq!(
+            run("for i in [1 2] { echo $i }").unwrap(),
+            [string("1"), string("2")]
+        );
+        let mut context = context();
+        run_in(&mut context, "let v = (if true { echo a; echo b })").unwrap();
+        assert_eq!(expr(&context, "$v").unwrap(), string("b"));
+    }
+
+    #[test]
+    fn custom_commands_keep_their_arguments_while_their_output_streams() {
+        let mut context = context();
+        run_in(&mut context, "def f [n] { for i in [1 2] { echo $n $i } }").unwrap();
+        assert_eq!(
+            run_in(&mut context, "f x").unwrap(),
+            [string("x 1"), string("x 2")]
+        );
+        assert!(expr(&context, "$n").is_err());
+    }
+
+    #[test]
+    fn errors_in_custom_commands_point_at_the_call() {
+        let mut context = context();
+        run_in(&mut context, "def f [] { pwd | first $nope }").unwrap();
+        run_in(&mut context, "def g [] { for i in [1] { first $nope } }").unwrap();
+        for source in ["pwd; f", "pwd; g"] {
+            let err = run_in(&mut context, source).unwrap_err();
+            assert_eq!(err.span(), Some(Span::new(5, 6)), "{}", source);
+        }
+    }
+
+    #[test]
+    fn custom_commands_nest_only_so_deep() {
+        let mut context = context();
+        run_in(&mut context, "def r [] { if true { for i in [1] { r } } }").unwrap();
+        let err = run_in(&mut context, "r").unwrap_err();
+        assert_eq!(
+            err.error,
+            ProximateShellError::LabeledError(
+                Span::new(0, 1),
+                String::from("calls of defined commands nest more than 64 deep")
+            )
+        );
+    }
+
+    #[test]
+    fn def_cant_replace_built_in_commands() {
+        let mut context = context();
+        assert!(run_in(&mut context, "def pwd [] { first 1 }").is_err());
+        run_in(&mut context, "def f [] { pwd }").unwrap();
+        run_in(&mut context, "def f [] { pwd | first 0 }").unwrap();
+        assert_eq!(run_in(&mut context, "f").unwrap(), []);
+    }
 }
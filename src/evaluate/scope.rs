@@ -0,0 +1,55 @@
+use crate::evaluate::Value;
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
+use indexmap::IndexMap;
+
+/// The variables visible to the commands being run, as a stack of frames.
+///
+/// Looking a variable up goes from the innermost frame outwards, while `let` always binds in the
+/// innermost frame, so a variable set inside a block disappears again when the block's frame is
+/// left.
+///
+/// Copies of a scope share their frames until one of them binds a variable, so a block can take a
+/// copy of its own for as long as its output needs it.
+#[derive(Debug, Clone)]
+pub struct Scope {
+    frames: Vec<Arc<IndexMap<String, Value>>>,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self {
+            frames: vec![Arc::new(IndexMap::new())],
+        }
+    }
+}
+
+impl Scope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new innermost frame.
+    pub fn enter(&mut self) {
+        self.frames.push(Arc::new(IndexMap::new()));
+    }
+
+    /// Drops the innermost frame and its variables. The outermost frame is never dropped.
+    pub fn exit(&mut self) {
+        if self.frames.len() > 1 {
+            self.frames.pop();
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.frames.iter().rev().find_map(|frame| frame.get(name))
+    }
+
+    /// Binds `name` in the innermost frame, shadowing any outer variable of the same name.
+    pub fn set(&mut self, name: impl Into<String>, value: Value) {
+        let frame = self
+            .frames
+            .last_mut()
+            .expect("a scope has at least one frame");
+        Arc::make_mut(frame).insert(name.into(), value);
+    }
+}
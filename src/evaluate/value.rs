@@ -1,12 +1,39 @@
+use crate::parser::{span::Span, token::SpannedToken};
 use alloc::{
     string::{String, ToString},
     vec::Vec,
 };
+use core::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+};
+use indexmap::IndexMap;
 use num_bigint::BigInt;
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Deserialize, Serialize)]
+/// The named columns of a single row, kept in insertion order.
+pub type Record = IndexMap<String, Value>;
+
+/// The pipelines of a `{ ... }` block, which are only run once a command like `if` asks for it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Block {
+    pub tokens: Vec<SpannedToken>,
+    pub span: Span,
+    /// The source the spans of the tokens point into
+    pub source: String,
+}
+
+impl Block {
+    /// The block as it was written, braces included.
+    #[inline]
+    pub fn text(&self) -> &str {
+        self.span.slice(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Value {
     Nothing,
     /// A "big int", an integer with arbitrarily large size (aka not limited to 64-bit)
@@ -21,19 +48,178 @@ pub enum Value {
     Path(String),
     Boolean(bool),
     List(Vec<Value>),
+    /// A row of named values, eg `{name: foo, size: 3}`
+    Record(Record),
+    /// A list of rows, eg the collected output of `ls`
+    Table(Vec<Record>),
+    /// A `{ ... }` block of pipelines
+    Block(Block),
+}
+
+impl Value {
+    /// The position of the variant, which orders values of different kinds.
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Nothing => 0,
+            Value::Int(_) => 1,
+            Value::Number(_) => 2,
+            Value::String(_) => 3,
+            Value::Pattern(_) => 4,
+            Value::Path(_) => 5,
+            Value::Boolean(_) => 6,
+            Value::List(_) => 7,
+            Value::Record(_) => 8,
+            Value::Table(_) => 9,
+            Value::Block(_) => 10,
+        }
+    }
+
+    /// The name of the kind of value, as shown in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nothing => "nothing",
+            Value::Int(_) => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Pattern(_) => "pattern",
+            Value::Path(_) => "path",
+            Value::Boolean(_) => "boolean",
+            Value::List(_) => "list",
+            Value::Record(_) => "record",
+            Value::Table(_) => "table",
+            Value::Block(_) => "block",
+        }
+    }
+
+    /// Whether the value counts as true for a condition. Nothing, false, zero and empty values
+    /// are false, anything else is true.
+    pub fn is_true(&self) -> bool {
+        match self {
+            Value::Nothing => false,
+            Value::Int(i) => i.sign() != num_bigint::Sign::NoSign,
+            Value::Number(n) => n.0 != 0.0,
+            Value::String(s) | Value::Pattern(s) | Value::Path(s) => !s.is_empty(),
+            Value::Boolean(b) => *b,
+            Value::List(list) => !list.is_empty(),
+            Value::Record(record) => !record.is_empty(),
+            Value::Table(rows) => !rows.is_empty(),
+            Value::Block(_) => true,
+        }
+    }
+}
+
+// `IndexMap` equality ignores the order of the entries, so records are compared entry by entry
+// instead, which keeps `Eq`, `Ord` and `Hash` consistent with each other.
+fn cmp_records(a: &Record, b: &Record) -> Ordering {
+    a.iter().cmp(b.iter())
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Nothing, Value::Nothing) => Ordering::Equal,
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Number(a), Value::Number(b)) => a.cmp(b),
+            (Value::String(a), Value::String(b))
+            | (Value::Pattern(a), Value::Pattern(b))
+            | (Value::Path(a), Value::Path(b)) => a.cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+            (Value::List(a), Value::List(b)) => a.cmp(b),
+            (Value::Record(a), Value::Record(b)) => cmp_records(a, b),
+            (Value::Table(a), Value::Table(b)) => a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| cmp_records(a, b))
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or_else(|| a.len().cmp(&b.len())),
+            (Value::Block(a), Value::Block(b)) => a.text().cmp(b.text()),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        fn hash_record<H: Hasher>(record: &Record, state: &mut H) {
+            record.len().hash(state);
+            for entry in record {
+                entry.hash(state);
+            }
+        }
+
+        self.rank().hash(state);
+        match self {
+            Value::Nothing => {}
+            Value::Int(i) => i.hash(state),
+            Value::Number(n) => n.hash(state),
+            Value::String(s) | Value::Pattern(s) | Value::Path(s) => s.hash(state),
+            Value::Boolean(b) => b.hash(state),
+            Value::List(list) => list.hash(state),
+            Value::Record(record) => hash_record(record, state),
+            Value::Table(rows) => {
+                rows.len().hash(state);
+                for row in rows {
+                    hash_record(row, state);
+                }
+            }
+            Value::Block(block) => block.text().hash(state),
+        }
+    }
+}
+
+struct DisplayRecord<'a>(&'a Record);
+
+impl fmt::Display for DisplayRecord<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("{")?;
+        for (i, (name, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}: {}", name, value)?;
+        }
+        f.write_str("}")
+    }
 }
 
-impl ToString for Value {
-    fn to_string(&self) -> String {
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Value::Int(i) => i.to_string(),
-            Value::Number(i) => i.to_string(),
-            Value::String(s) => s.clone(),
-            Value::Pattern(s) => s.clone(),
-            Value::Path(s) => s.clone(),
-            Value::Boolean(b) => b.to_string(),
-            Value::List(v) => v.iter().map(Self::to_string).collect::<Vec<_>>().join(" "),
-            Value::Nothing => String::new(),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Number(i) => write!(f, "{}", i),
+            Value::String(s) => f.write_str(s),
+            Value::Pattern(s) => f.write_str(s),
+            Value::Path(s) => f.write_str(s),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::List(v) => {
+                f.write_str(&v.iter().map(Self::to_string).collect::<Vec<_>>().join(" "))
+            }
+            Value::Record(record) => write!(f, "{}", DisplayRecord(record)),
+            Value::Table(rows) => {
+                for (i, row) in rows.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("\n")?;
+                    }
+                    write!(f, "{}", DisplayRecord(row))?;
+                }
+                Ok(())
+            }
+            Value::Block(block) => f.write_str(block.text()),
+            Value::Nothing => Ok(()),
         }
     }
 }
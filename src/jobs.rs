@@ -0,0 +1,260 @@
+use crate::{
+    error::ShellError,
+    shell::{Process, Shell, Signal},
+};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::{
+    fmt,
+    future::{poll_fn, Future},
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Poll, Waker},
+};
+use spin::RwLock;
+
+/// Where a background job is at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    /// Paused by `kill --stop`, until `bg` or `fg` resumes it
+    Stopped,
+    /// Finished with the given exit status
+    Done(i32),
+    /// Interrupted by `kill` before it finished
+    Killed,
+}
+
+impl JobState {
+    #[inline]
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobState::Done(_) | JobState::Killed)
+    }
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobState::Running => f.write_str("running"),
+            JobState::Stopped => f.write_str("stopped"),
+            JobState::Done(0) => f.write_str("done"),
+            JobState::Done(status) => write!(f, "exit {}", status),
+            JobState::Killed => f.write_str("killed"),
+        }
+    }
+}
+
+struct Shared {
+    state: JobState,
+    /// The external commands the job started
+    processes: Vec<Process>,
+    /// Wakes the job once it is resumed
+    resume: Option<Waker>,
+    /// Wakes whoever waits for the job to finish
+    finish: Option<Waker>,
+}
+
+/// A pipeline started in the background with `&`, shared between the job table and the task
+/// that runs it.
+#[derive(Clone)]
+pub struct Job {
+    pub id: usize,
+    /// The pipeline as it was typed
+    pub command: String,
+    /// Interrupts the job at the next value it produces, like ctrl-c does for the pipeline in the
+    /// foreground
+    pub ctrl_c: Arc<AtomicBool>,
+    shared: Arc<RwLock<Shared>>,
+}
+
+impl Job {
+    fn new(id: usize, command: String) -> Self {
+        Self {
+            id,
+            command,
+            ctrl_c: Arc::new(AtomicBool::new(false)),
+            shared: Arc::new(RwLock::new(Shared {
+                state: JobState::Running,
+                processes: Vec::new(),
+                resume: None,
+                finish: None,
+            })),
+        }
+    }
+
+    #[inline]
+    pub fn state(&self) -> JobState {
+        self.shared.read().state
+    }
+
+    /// Records a process the job started, so that stopping and killing the job apply to it. One
+    /// started after the job was stopped or killed is stopped or killed right away.
+    pub fn add_process(&self, shell: &dyn Shell, process: Process) -> Result<(), ShellError> {
+        let state = {
+            let mut shared = self.shared.write();
+            shared.processes.retain(Process::is_running);
+            shared.processes.push(process.clone());
+            shared.state
+        };
+        if self.ctrl_c.load(Ordering::Acquire) {
+            shell.signal(&process, Signal::Kill)
+        } else if state == JobState::Stopped {
+            shell.signal(&process, Signal::Stop)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The processes of the job that are still running.
+    fn processes(&self) -> Vec<Process> {
+        let shared = self.shared.read();
+        shared
+            .processes
+            .iter()
+            .filter(|process| process.is_running())
+            .cloned()
+            .collect()
+    }
+
+    /// Sends `signal` to every process of the job that is still running.
+    fn signal(&self, shell: &dyn Shell, signal: Signal) -> Result<(), ShellError> {
+        self.processes()
+            .iter()
+            .try_for_each(|process| shell.signal(process, signal))
+    }
+
+    /// Pauses a running job, stopping its processes and then the job itself before the next value
+    /// it produces. Returns whether it was running.
+    pub fn stop(&self, shell: &dyn Shell) -> Result<bool, ShellError> {
+        if self.state() != JobState::Running {
+            return Ok(false);
+        }
+        self.signal(shell, Signal::Stop)?;
+        let mut shared = self.shared.write();
+        // It may have finished while its processes were being stopped.
+        let running = shared.state == JobState::Running;
+        if running {
+            shared.state = JobState::Stopped;
+        }
+
+        Ok(running)
+    }
+
+    /// Lets a stopped job and its processes carry on. Returns whether it was stopped.
+    pub fn resume(&self, shell: &dyn Shell) -> Result<bool, ShellError> {
+        if self.state() != JobState::Stopped {
+            return Ok(false);
+        }
+        self.signal(shell, Signal::Continue)?;
+        let mut shared = self.shared.write();
+        let stopped = shared.state == JobState::Stopped;
+        if stopped {
+            shared.state = JobState::Running;
+            if let Some(waker) = shared.resume.take() {
+                waker.wake();
+            }
+        }
+
+        Ok(stopped)
+    }
+
+    /// Interrupts the job and kills its processes, resuming the job first if it is stopped so
+    /// that it gets to see the flag. It is reported as killed once it has finished.
+    pub fn kill(&self, shell: &dyn Shell) -> Result<(), ShellError> {
+        self.ctrl_c.store(true, Ordering::Release);
+        self.signal(shell, Signal::Kill)?;
+        self.resume(shell)?;
+
+        Ok(())
+    }
+
+    /// Records that the task running the job is done, as killed if it was interrupted.
+    pub fn finish(&self, status: i32) {
+        let mut shared = self.shared.write();
+        shared.state = if self.ctrl_c.load(Ordering::Acquire) {
+            JobState::Killed
+        } else {
+            JobState::Done(status)
+        };
+        if let Some(waker) = shared.finish.take() {
+            waker.wake();
+        }
+    }
+
+    /// Waits for as long as the job is stopped.
+    pub fn running(&self) -> impl Future<Output = ()> + Send + '_ {
+        poll_fn(move |cx| {
+            let mut shared = self.shared.write();
+            if shared.state == JobState::Stopped {
+                shared.resume = Some(cx.waker().clone());
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+    }
+
+    /// Waits for the job to finish, returning how it ended.
+    pub fn finished(&self) -> impl Future<Output = JobState> + Send + '_ {
+        poll_fn(move |cx| {
+            let mut shared = self.shared.write();
+            if shared.state.is_finished() {
+                Poll::Ready(shared.state)
+            } else {
+                shared.finish = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+/// The background jobs of a shell, numbered from 1 in the order they were started.
+///
+/// Finished jobs stay in the table until they are reported, so that a notice can be printed
+/// before the next prompt.
+#[derive(Default)]
+pub struct JobTable {
+    jobs: Vec<Job>,
+}
+
+impl JobTable {
+    /// Adds a job for `command`, numbered one past the newest job in the table.
+    pub fn add(&mut self, command: String) -> Job {
+        let id = self.jobs.last().map_or(1, |job| job.id + 1);
+        let job = Job::new(id, command);
+        self.jobs.push(job.clone());
+
+        job
+    }
+
+    #[inline]
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// Finds the job with the given id, or the newest one when there is no id.
+    pub fn find(&self, id: Option<usize>) -> Result<Job, ShellError> {
+        let job = match id {
+            Some(id) => self.jobs.iter().find(|job| job.id == id),
+            None => self.jobs.last(),
+        };
+
+        job.cloned().ok_or_else(|| match id {
+            Some(id) => ShellError::runtime_error(format!("there is no job {}", id)),
+            None => ShellError::runtime_error("there are no jobs"),
+        })
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        self.jobs.retain(|job| job.id != id);
+    }
+
+    /// Takes the jobs that have finished out of the table, to report them.
+    pub fn take_finished(&mut self) -> Vec<Job> {
+        let (finished, running) = self
+            .jobs
+            .drain(..)
+            .partition(|job| job.state().is_finished());
+        self.jobs = running;
+
+        finished
+    }
+}
@@ -7,14 +7,22 @@ extern crate core;
 
 pub mod cli;
 pub mod commands;
+pub mod completion;
 pub mod context;
 pub mod deserializer;
 pub mod error;
 pub mod evaluate;
+pub mod jobs;
 pub mod parser;
+pub mod render;
 pub mod shell;
 pub mod signature;
+pub mod stream;
 
 pub use cli::cli;
 pub use error::ShellError;
 pub use shell::Shell;
+
+/// A boxed future that can move between threads, as returned by commands and shells.
+pub type BoxFuture<'a, T> =
+    core::pin::Pin<alloc::boxed::Box<dyn core::future::Future<Output = T> + Send + 'a>>;
@@ -8,13 +8,21 @@ pub type ExternalArg = String;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ExternalArgs {
+    /// The arguments as they are passed to the program, which are only final once the tokens
+    /// have been evaluated
     pub list: Vec<ExternalArg>,
+    /// The tokens the arguments were written as
+    pub tokens: Vec<SpannedToken>,
     pub span: Span,
 }
 
 impl ExternalArgs {
     pub fn new(list: Vec<ExternalArg>, span: Span) -> Self {
-        Self { list, span }
+        Self {
+            list,
+            tokens: vec![],
+            span,
+        }
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &ExternalArg> {
@@ -26,17 +34,22 @@ impl ExternalArgs {
         source: &str,
         span: Span,
     ) -> Self {
+        let tokens = tokens
+            .filter(|spanned| {
+                !matches!(
+                    spanned.item,
+                    Token::Separator | Token::Pipe | Token::And | Token::Or | Token::Whitespace
+                )
+            })
+            .collect::<Vec<_>>();
         let list = tokens
+            .iter()
             .map(|spanned| match spanned.item {
-                Token::String(s) => Some(s.string(source)),
-                Token::Bare | Token::ExternalWord | Token::Flag(_) | Token::GlobPattern => {
-                    Some(spanned.span.string(source))
-                }
-                Token::Separator | Token::Whitespace => None,
+                Token::String(s) => s.string(source),
+                _ => spanned.span.string(source),
             })
-            .flatten()
-            .collect::<Vec<_>>();
-        Self { list, span }
+            .collect();
+        Self { list, tokens, span }
     }
 }
 
@@ -53,6 +66,8 @@ pub struct ExternalCommand {
     pub name: String,
     pub name_span: Span,
     pub args: ExternalArgs,
+    /// The file named by a `2>`, which the error output of the program is written to
+    pub errors: Option<SpannedToken>,
 }
 
 impl ExternalCommand {
@@ -61,6 +76,7 @@ impl ExternalCommand {
             name,
             name_span,
             args,
+            errors: None,
         }
     }
 }
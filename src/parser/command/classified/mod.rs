@@ -1,6 +1,16 @@
-use crate::parser::span::{HasSpan, Span};
-use alloc::vec::Vec;
-use external::ExternalCommand;
+use crate::{
+    context::{Aliases, CommandRegistry},
+    error::{ProximateShellError, ShellError},
+    parser::{
+        command::parse_command_tail,
+        hir,
+        span::{span_for_spanned_list, HasSpan, Span, Spanned},
+        token::{Redirection, SpannedToken, Token},
+        unquote,
+    },
+};
+use alloc::{string::String, vec::Vec};
+use external::{ExternalArgs, ExternalCommand};
 use internal::InternalCommand;
 
 pub mod external;
@@ -26,6 +36,15 @@ impl core::ops::Deref for Commands {
     }
 }
 
+impl ClassifiedCommand {
+    pub fn name_span(&self) -> Span {
+        match self {
+            ClassifiedCommand::Internal(command) => command.name_span,
+            ClassifiedCommand::External(command) => command.name_span,
+        }
+    }
+}
+
 impl HasSpan for ClassifiedCommand {
     fn span(&self) -> Span {
         match self {
@@ -35,9 +54,58 @@ impl HasSpan for ClassifiedCommand {
     }
 }
 
+/// When a pipeline runs, depending on how the pipelines before it went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// At the start, or after a `;` or newline: whatever happened before it
+    Always,
+    /// After `&&`: when the last pipeline that ran succeeded
+    Succeeded,
+    /// After `||`: when the last pipeline that ran failed
+    Failed,
+}
+
+impl Condition {
+    /// Whether a pipeline with this condition runs after the last pipeline that ran.
+    #[inline]
+    pub fn runs_after(self, succeeded: bool) -> bool {
+        match self {
+            Condition::Always => true,
+            Condition::Succeeded => succeeded,
+            Condition::Failed => !succeeded,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ClassifiedPipeline {
     pub commands: Commands,
+    pub condition: Condition,
+    /// The file named by a `<`, read as the input of the first command
+    pub input: Option<SpannedToken>,
+    /// The file named by a `>` or `>>`, which the output of the pipeline is written to
+    pub output: Option<(Redirection, SpannedToken)>,
+    /// Whether the pipeline ends in `&`, to run it as a background job
+    pub background: bool,
+    /// The commands that were expanded from aliases
+    pub expansions: Vec<Expansion>,
+}
+
+/// A command that was expanded from an alias. The text of the alias is appended to the source for
+/// its tokens to point into, and errors that point into it are moved to the alias as it was typed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expansion {
+    /// Where the text of the alias was appended to the source
+    pub text: Span,
+    /// The alias as it was typed
+    pub alias: Span,
+}
+
+impl Expansion {
+    /// Points an error in the text of the alias at the alias instead.
+    pub fn locate(&self, err: ShellError) -> ShellError {
+        err.relocate(self.text, self.alias)
+    }
 }
 
 impl ClassifiedPipeline {
@@ -47,6 +115,11 @@ impl ClassifiedPipeline {
                 list,
                 span: span.into(),
             },
+            condition: Condition::Always,
+            input: None,
+            output: None,
+            background: false,
+            expansions: vec![],
         }
     }
 }
@@ -56,3 +129,485 @@ impl HasSpan for ClassifiedPipeline {
         self.commands.span
     }
 }
+
+/// Classifies a parsed token list into pipelines, as [`classify_pipeline_with_aliases`] does
+/// without any aliases to expand.
+pub fn classify_pipeline(
+    tokens: Spanned<Vec<SpannedToken>>,
+    registry: &CommandRegistry,
+    source: &str,
+) -> Result<Vec<ClassifiedPipeline>, ShellError> {
+    // Nothing is appended to it without aliases.
+    let mut source = String::from(source);
+    classify_pipeline_with_aliases(tokens, registry, &Aliases::new(), &mut source)
+}
+
+/// Classifies a parsed token list into pipelines, one for each part separated by `;`, a newline,
+/// `&`, `&&` or `||`, which also decide the [`Condition`] of the pipeline after them. A pipeline
+/// that ends in `&` is marked to run in the background.
+///
+/// Each pipeline holds one command per `|` separated stage. A command whose name is found in
+/// `registry` becomes an [`InternalCommand`] with its arguments checked against the command's
+/// `Signature`, anything else becomes an [`ExternalCommand`]. A command whose name is one of
+/// `aliases` is expanded first, with the text of the alias appended to `source`.
+pub fn classify_pipeline_with_aliases(
+    tokens: Spanned<Vec<SpannedToken>>,
+    registry: &CommandRegistry,
+    aliases: &Aliases,
+    source: &mut String,
+) -> Result<Vec<ClassifiedPipeline>, ShellError> {
+    // The tokens of each pipeline, with its condition and the `&&` or `||` that set it
+    let mut groups: Vec<(Condition, Option<Span>, Vec<SpannedToken>)> =
+        vec![(Condition::Always, None, vec![])];
+    for token in tokens.item {
+        let (_, operator, group) = groups.last_mut().unwrap();
+        let span = token.span;
+        let condition = match token.item {
+            Token::Whitespace => continue,
+            // The command after `|`, `&&` or `||` can be on the next line.
+            Token::Separator
+                if token.span.slice(source) == "\n"
+                    && match group.last() {
+                        None => operator.is_some(),
+                        Some(last) => last.item == Token::Pipe,
+                    } =>
+            {
+                continue
+            }
+            Token::Separator => Condition::Always,
+            // Kept at the end of its pipeline, to mark it as a background job
+            Token::Background if !group.is_empty() => {
+                group.push(token);
+                Condition::Always
+            }
+            Token::Background => {
+                return Err(ProximateShellError::ParseError(
+                    token.span,
+                    Some(String::from("expected a command before &")),
+                )
+                .start())
+            }
+            Token::And => Condition::Succeeded,
+            Token::Or => Condition::Failed,
+            _ => {
+                group.push(token);
+                continue;
+            }
+        };
+        if group.is_empty() {
+            if let Some(operator) = operator {
+                return Err(missing_command(*operator, source));
+            }
+            if condition != Condition::Always {
+                return Err(missing_command(span, source));
+            }
+        }
+        let operator = (condition != Condition::Always).then_some(span);
+        groups.push((condition, operator, vec![]));
+    }
+    // More input can still supply the command after a trailing `&&` or `||`.
+    if let Some((_, Some(operator), group)) = groups.last() {
+        if group.is_empty() {
+            return Err(ProximateShellError::IncompleteInput(
+                *operator,
+                format!("expected a command after {}", operator.slice(source)),
+            )
+            .start());
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|(_, _, group)| !group.is_empty())
+        .map(|(condition, _, mut group)| {
+            let background = group
+                .last()
+                .is_some_and(|token| token.item == Token::Background);
+            if background {
+                group.pop();
+            }
+            let mut pipeline = classify_stages(group, registry, aliases, source)?;
+            pipeline.condition = condition;
+            pipeline.background = background;
+            Ok(pipeline)
+        })
+        .collect()
+}
+
+/// The error for a `&&` or `||` at `operator` that doesn't have a command on one of its sides
+fn missing_command(operator: Span, source: &str) -> ShellError {
+    ProximateShellError::ParseError(
+        operator,
+        Some(format!(
+            "expected a command on both sides of {}",
+            operator.slice(source)
+        )),
+    )
+    .start()
+}
+
+/// A redirection of a stage: its kind, the span it covers and the file it names
+type Redirect = (Redirection, Span, SpannedToken);
+
+/// Classifies the `|` separated stages of one pipeline, taking their redirections out of their
+/// arguments. Only the first stage can read from a file with `<`, only the last can write its
+/// output to one with `>` or `>>`, and `2>` is only for external commands.
+fn classify_stages(
+    tokens: Vec<SpannedToken>,
+    registry: &CommandRegistry,
+    aliases: &Aliases,
+    source: &mut String,
+) -> Result<ClassifiedPipeline, ShellError> {
+    let span = span_for_spanned_list(tokens.iter().map(|token| token.span));
+    // The tokens of each stage, with its redirections
+    let mut stages: Vec<(Vec<SpannedToken>, Vec<Redirect>)> = vec![(vec![], vec![])];
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        let (stage, redirects) = stages.last_mut().unwrap();
+        match token.item {
+            Token::Pipe => {
+                if stage.is_empty() || tokens.peek().is_none() {
+                    return Err(ProximateShellError::ParseError(
+                        token.span,
+                        Some(String::from("expected a command on both sides of the pipe")),
+                    )
+                    .start());
+                }
+                stages.push((vec![], vec![]));
+            }
+            Token::Redirect(kind, target) => redirects.push((kind, token.span, *target)),
+            _ => stage.push(token),
+        }
+    }
+
+    let last = stages.len() - 1;
+    let mut pipeline = ClassifiedPipeline::commands(vec![], span);
+    for (index, (stage, redirects)) in stages.into_iter().enumerate() {
+        if let (true, Some((_, span, _))) = (stage.is_empty(), redirects.first()) {
+            return Err(redirect_error(*span, "expected a command to redirect"));
+        }
+        let appended = source.len();
+        let mut command = classify_command(stage, registry, aliases, source)?;
+        if source.len() > appended {
+            pipeline.expansions.push(Expansion {
+                text: Span::new(appended, source.len()),
+                alias: command.name_span(),
+            });
+        }
+        for (kind, span, target) in redirects {
+            match (kind, &mut command) {
+                (Redirection::Input, _) if index > 0 => {
+                    return Err(redirect_error(
+                        span,
+                        "only the first command of a pipeline can read from a file",
+                    ))
+                }
+                (Redirection::Input, _) => pipeline.input = Some(target),
+                (Redirection::Output, _) | (Redirection::Append, _) if index < last => {
+                    return Err(redirect_error(
+                        span,
+                        "only the last command of a pipeline can write to a file",
+                    ))
+                }
+                (Redirection::Output, _) | (Redirection::Append, _) => {
+                    pipeline.output = Some((kind, target))
+                }
+                (Redirection::Errors, ClassifiedCommand::External(command)) => {
+                    command.errors = Some(target)
+                }
+                (Redirection::Errors, ClassifiedCommand::Internal(_)) => {
+                    return Err(redirect_error(
+                        span,
+                        "only external commands have error output to redirect",
+                    ))
+                }
+            }
+        }
+        pipeline.commands.list.push(command);
+    }
+
+    Ok(pipeline)
+}
+
+fn redirect_error(span: Span, message: &str) -> ShellError {
+    ProximateShellError::ParseError(span, Some(String::from(message))).start()
+}
+
+/// Classifies one command, expanding its name first when it is an alias. The alias can stand for
+/// another alias, until one of them comes up again or the name isn't an alias.
+///
+/// The tokens of an alias point into its text, which is appended to `source`. The spans of the
+/// command and of its name still point at what was typed, and so do the errors in the expansion.
+fn classify_command(
+    mut tokens: Vec<SpannedToken>,
+    registry: &CommandRegistry,
+    aliases: &Aliases,
+    source: &mut String,
+) -> Result<ClassifiedCommand, ShellError> {
+    let span = span_for_spanned_list(tokens.iter().map(|token| token.span));
+    let name_span = tokens[0].span;
+    let appended = source.len();
+    let mut expanded: Vec<String> = vec![];
+    let name = loop {
+        let name = command_name(&tokens[0], source)?;
+        match aliases.get(&name) {
+            Some(alias) if !expanded.contains(&name) => {
+                let offset = source.len();
+                source.push_str(&alias.text);
+                let expansion = alias
+                    .tokens
+                    .iter()
+                    .map(|token| token.clone().shifted(offset));
+                tokens.splice(..1, expansion);
+                expanded.push(name);
+            }
+            _ => break name,
+        }
+    };
+    let command = classify_call(tokens, name, name_span, span, registry, source);
+    if expanded.is_empty() {
+        return command;
+    }
+    let expansion = Expansion {
+        text: Span::new(appended, source.len()),
+        alias: name_span,
+    };
+
+    command.map_err(|err| expansion.locate(err))
+}
+
+/// The name of the command that `head` starts
+fn command_name(head: &SpannedToken, source: &str) -> Result<String, ShellError> {
+    match &head.item {
+        Token::String(s) => unquote(head, *s, source),
+        Token::Bare | Token::ExternalWord | Token::GlobPattern => Ok(head.span.string(source)),
+        Token::Invocation(_) => Err(ProximateShellError::ParseError(
+            head.span,
+            Some(String::from(
+                "a command substitution can't be run as a command, only passed as an argument",
+            )),
+        )
+        .start()),
+        token => Err(ProximateShellError::ParseError(
+            head.span,
+            Some(format!("unexpected {}", token.desc())),
+        )
+        .start()),
+    }
+}
+
+fn classify_call(
+    tokens: Vec<SpannedToken>,
+    name: String,
+    name_span: Span,
+    span: Span,
+    registry: &CommandRegistry,
+    source: &str,
+) -> Result<ClassifiedCommand, ShellError> {
+    let mut tokens = tokens.into_iter();
+    let head = tokens.next().expect("a command has at least one token");
+
+    match registry.get(&name) {
+        Some(signature) => {
+            let (positional, named) =
+                parse_command_tail(&signature, &mut tokens, name_span, source)?
+                    .unwrap_or((None, None));
+            let call = hir::Call {
+                head,
+                positional,
+                named,
+                span,
+            };
+            Ok(ClassifiedCommand::Internal(InternalCommand::new(
+                name, name_span, call,
+            )))
+        }
+        None => {
+            let args = ExternalArgs::from_tokens(&mut tokens, source, span);
+            Ok(ClassifiedCommand::External(ExternalCommand::new(
+                name, name_span, args,
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::Pwd, context::Alias, parser::parse};
+    use alloc::sync::Arc;
+
+    fn registry() -> CommandRegistry {
+        let registry = CommandRegistry::empty();
+        registry.insert("pwd", Arc::new(Pwd));
+        registry
+    }
+
+    fn aliases(list: &[(&str, &str)]) -> Aliases {
+        list.iter()
+            .map(|(name, text)| {
+                let alias = Alias {
+                    text: String::from(*text),
+                    tokens: parse(text).unwrap().item,
+                };
+                (String::from(*name), alias)
+            })
+            .collect()
+    }
+
+    fn classify(source: &str) -> Result<Vec<ClassifiedPipeline>, ShellError> {
+        classify_pipeline(parse(source)?, &registry(), source)
+    }
+
+    fn names(pipeline: &ClassifiedPipeline) -> Vec<&str> {
+        pipeline
+            .commands
+            .iter()
+            .map(|command| match command {
+                ClassifiedCommand::Internal(command) => command.name.as_str(),
+                ClassifiedCommand::External(command) => command.name.as_str(),
+            })
+            .collect()
+    }
+
+    fn error_at(source: &str) -> Option<&str> {
+        let err = classify(source).unwrap_err();
+        err.span().map(|span| span.slice(source))
+    }
+
+    #[test]
+    fn commands_are_internal_or_external() {
+        let pipelines = classify("pwd | wc -l").unwrap();
+        assert_eq!(pipelines.len(), 1);
+        assert!(matches!(
+            pipelines[0].commands.list.as_slice(),
+            [
+                ClassifiedCommand::Internal(_),
+                ClassifiedCommand::External(_)
+            ]
+        ));
+        assert_eq!(names(&pipelines[0]), ["pwd", "wc"]);
+    }
+
+    #[test]
+    fn operators_set_the_condition_of_the_next_pipeline() {
+        let pipelines = classify("a && b || c; d\ne").unwrap();
+        let conditions: Vec<Condition> = pipelines.iter().map(|p| p.condition).collect();
+        assert_eq!(
+            conditions,
+            [
+                Condition::Always,
+                Condition::Succeeded,
+                Condition::Failed,
+                Condition::Always,
+                Condition::Always
+            ]
+        );
+        assert_eq!(names(&pipelines[4]), ["e"]);
+    }
+
+    #[test]
+    fn operators_need_commands_on_both_sides() {
+        assert_eq!(error_at("&& a"), Some("&&"));
+        assert_eq!(error_at("a || ; b"), Some("||"));
+        assert!(classify("a &&").unwrap_err().is_incomplete());
+        // The command after an operator can be on the next line.
+        assert_eq!(classify("a ||\nb").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn background_pipelines() {
+        let pipelines = classify("a & b &").unwrap();
+        assert_eq!(pipelines.len(), 2);
+        assert!(pipelines.iter().all(|pipeline| pipeline.background));
+        assert_eq!(names(&pipelines[1]), ["b"]);
+        assert!(!classify("a; b").unwrap()[0].background);
+        assert_eq!(error_at("& a"), Some("&"));
+    }
+
+    #[test]
+    fn redirections() {
+        let source = "a < in | b 2> err > out";
+        let pipeline = &classify(source).unwrap()[0];
+        assert_eq!(names(pipeline), ["a", "b"]);
+        let slice = |token: &SpannedToken| token.span.slice(source);
+        assert_eq!(pipeline.input.as_ref().map(slice), Some("in"));
+        let (kind, target) = pipeline.output.as_ref().unwrap();
+        assert_eq!((*kind, slice(target)), (Redirection::Output, "out"));
+        match &pipeline.commands[1] {
+            ClassifiedCommand::External(command) => {
+                assert_eq!(command.errors.as_ref().map(slice), Some("err"))
+            }
+            command => panic!("expected an external command, got {:?}", command),
+        }
+
+        let pipeline = &classify("a >> log").unwrap()[0];
+        assert_eq!(pipeline.output.as_ref().unwrap().0, Redirection::Append);
+    }
+
+    #[test]
+    fn redirections_only_go_where_they_apply() {
+        assert_eq!(error_at("a | b < in"), Some("< in"));
+        assert_eq!(error_at("a > out | b"), Some("> out"));
+        assert_eq!(error_at("pwd 2> err"), Some("2> err"));
+        assert_eq!(error_at("> out"), Some("> out"));
+    }
+
+    #[test]
+    fn aliases_are_expanded() {
+        let source = "here | nested";
+        let mut expanded = String::from(source);
+        let aliases = aliases(&[("here", "pwd"), ("nested", "here"), ("loop", "loop")]);
+        let pipelines = classify_pipeline_with_aliases(
+            parse(source).unwrap(),
+            &registry(),
+            &aliases,
+            &mut expanded,
+        )
+        .unwrap();
+        assert_eq!(names(&pipelines[0]), ["pwd", "pwd"]);
+        assert_eq!(expanded, "here | nestedpwdherepwd");
+        let alias_spans: Vec<&str> = pipelines[0]
+            .expansions
+            .iter()
+            .map(|expansion| expansion.alias.slice(source))
+            .collect();
+        assert_eq!(alias_spans, ["here", "nested"]);
+
+        // An alias that stands for itself runs the command of that name.
+        let source = "loop";
+        let mut expanded = String::from(source);
+        let pipelines = classify_pipeline_with_aliases(
+            parse(source).unwrap(),
+            &registry(),
+            &aliases,
+            &mut expanded,
+        )
+        .unwrap();
+        assert_eq!(names(&pipelines[0]), ["loop"]);
+    }
+
+    #[test]
+    fn errors_in_aliases_point_at_the_alias() {
+        let source = "a | bad";
+        let mut expanded = String::from(source);
+        let aliases = aliases(&[("bad", "pwd extra")]);
+        let err = classify_pipeline_with_aliases(
+            parse(source).unwrap(),
+            &registry(),
+            &aliases,
+            &mut expanded,
+        )
+        .unwrap_err();
+        assert_eq!(err.span().map(|span| span.slice(source)), Some("bad"));
+    }
+
+    #[test]
+    fn substitutions_are_not_commands() {
+        let source = "(pwd | length)";
+        let err = classify(source).unwrap_err();
+        assert_eq!(err.span().map(|span| span.slice(source)), Some(source));
+        assert!(err.to_string().contains("only passed as an argument"));
+        assert_eq!(classify("wc (pwd)").unwrap().len(), 1);
+    }
+}
@@ -13,6 +13,64 @@ pub mod classified;
 
 type OptionalHeadTail = (Option<Vec<SpannedToken>>, Option<NamedArguments>);
 
+/// Records the flag `name` found at `span`, taking the token after it as its value when the flag
+/// has one. Returns `false` when a mandatory value is missing.
+fn insert_named(
+    named: &mut NamedArguments,
+    name: &str,
+    kind: &NamedType,
+    span: Span,
+    tail: &mut impl Iterator<Item = SpannedToken>,
+) -> bool {
+    let mut value = || tail.find(|token| token.item != Token::Whitespace);
+    match kind {
+        NamedType::Switch(_) => named.insert_switch(name, Some(span)),
+        NamedType::Mandatory(..) => match value() {
+            Some(value) => named.insert_mandatory(name, value),
+            None => return false,
+        },
+        NamedType::Optional(..) => named.insert_optional(name, value()),
+    }
+
+    true
+}
+
+/// The error for a `flag` that `config` doesn't have, suggesting the flag whose name is closest to
+/// `name` when it looks like a typo of it.
+fn unknown_flag(config: &Signature, flag: &str, name: &str, span: Span) -> ShellError {
+    let closest = config
+        .named
+        .keys()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.chars().count() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance);
+    let reason = match closest {
+        Some((_, suggestion)) => format!(
+            "{} has no flag {}, did you mean --{}?",
+            config.name, flag, suggestion
+        ),
+        None => format!("{} has no flag {}", config.name, flag),
+    };
+
+    ProximateShellError::ParseError(span, Some(reason)).start()
+}
+
+/// The number of single character insertions, deletions and substitutions that turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
 pub fn parse_command_tail(
     config: &Signature,
     tail: &mut impl Iterator<Item = SpannedToken>,
@@ -23,40 +81,100 @@ pub fn parse_command_tail(
     let mut positional: Vec<SpannedToken> = vec![];
     let mut rest_signature = config.clone();
     while let Some(spanned) = tail.next() {
+        // Once the positionals are given, the rest is taken as one word, from the first token to
+        // the last, with the quoting, spacing and flags it was typed with.
+        if config.rest_as_typed
+            && rest_signature.positional.is_empty()
+            && spanned.item != Token::Whitespace
+        {
+            let last = tail.filter(|token| token.item != Token::Whitespace).last();
+            let span = spanned.span.until_option(last.map(|token| token.span));
+            positional.push(SpannedToken::new(span, Token::Bare));
+            break;
+        }
         match spanned.item {
-            Token::String(_) | Token::Bare | Token::ExternalWord | Token::GlobPattern => {
+            Token::String(_)
+            | Token::Bare
+            | Token::ExternalWord
+            | Token::GlobPattern
+            | Token::Variable(_)
+            | Token::Invocation(_)
+            | Token::Interpolation(_)
+            | Token::List(_)
+            | Token::Record(_)
+            | Token::Block(_) => {
                 if !rest_signature.positional.is_empty() {
                     positional.push(spanned);
                     rest_signature.shift_positional();
                 } else if config.rest_positional.is_some() {
                     positional.push(spanned);
+                } else {
+                    let reason = match config.positional.len() {
+                        0 => format!("{} takes no positional arguments", config.name),
+                        1 => format!("{} takes at most 1 positional argument", config.name),
+                        n => format!("{} takes at most {} positional arguments", config.name, n),
+                    };
+                    return Err(ProximateShellError::ParseError(spanned.span, Some(reason)).start());
                 }
             }
             Token::Flag(flag) => {
-                if let Some((kind, _)) = config.named.get(flag.slice(source)) {
-                    let kind: &NamedType = kind; // just type annotation
-                    match kind {
-                        NamedType::Switch => {
-                            rest_signature.remove_named(flag.slice(source));
-                            named.insert_switch(flag.slice(source), Some(flag));
-                        }
-                        NamedType::Mandatory(_) => {
-                            if let Some(next_token) = tail.next() {
-                                rest_signature.remove_named(flag.slice(source));
-                                named.insert_mandatory(flag.slice(source), next_token);
-                            } else {
-                                break;
-                            }
-                        }
-                        NamedType::Optional(_) => {
-                            let next_token = tail.next();
-                            rest_signature.remove_named(flag.slice(source));
-                            named.insert_optional(flag.slice(source), next_token);
-                        }
+                let name = flag.slice(source);
+                let (kind, _) = config.named.get(name).ok_or_else(|| {
+                    unknown_flag(config, spanned.span.slice(source), name, spanned.span)
+                })?;
+                if insert_named(&mut named, name, kind, flag, tail) {
+                    rest_signature.remove_named(name);
+                }
+            }
+            Token::ShortFlag(flags) => {
+                let mut shorts = flags.slice(source).char_indices().peekable();
+                while let Some((offset, short)) = shorts.next() {
+                    let start = flags.start() + offset;
+                    let span = Span::new(start, start + short.len_utf8());
+                    // A mistyped `--name` with a single dash is suggested as the long flag.
+                    let (name, kind) = config.short_named(short).ok_or_else(|| {
+                        unknown_flag(config, &format!("-{}", short), flags.slice(source), span)
+                    })?;
+                    if !matches!(kind, NamedType::Switch(_)) && shorts.peek().is_some() {
+                        return Err(ProximateShellError::ParseError(
+                            span,
+                            Some(format!("-{} takes a value, so it must come last", short)),
+                        )
+                        .start());
+                    }
+                    if insert_named(&mut named, name, kind, span, tail) {
+                        rest_signature.remove_named(name);
                     }
                 }
             }
-            Token::Whitespace | Token::Separator => {}
+            Token::FlagWithValue(flag, ref value) => {
+                let name = flag.slice(source);
+                let (kind, _) = config.named.get(name).ok_or_else(|| {
+                    unknown_flag(config, &format!("--{}", name), name, spanned.span)
+                })?;
+                match kind {
+                    NamedType::Switch(_) => match value.span.slice(source) {
+                        "true" => named.insert_switch(name, Some(flag)),
+                        "false" => named.insert_switch(name, None),
+                        _ => {
+                            return Err(ProximateShellError::ParseError(
+                                value.span,
+                                Some(format!("--{} is a switch, expected true or false", name)),
+                            )
+                            .start())
+                        }
+                    },
+                    _ => named.insert_mandatory(name, (**value).clone()),
+                }
+                rest_signature.remove_named(name);
+            }
+            Token::Whitespace
+            | Token::Separator
+            | Token::Pipe
+            | Token::And
+            | Token::Or
+            | Token::Background
+            | Token::Redirect(..) => {}
         }
     }
     let mut err: Option<(Span, Option<String>)> = None;
@@ -89,8 +207,8 @@ pub fn parse_command_tail(
     if let Some((span, reason)) = err {
         Err(ProximateShellError::ParseError(span, reason).start())
     } else {
-        let positional = (!positional.is_empty()).then(|| positional);
-        let named = (!named.named.is_empty()).then(|| named);
+        let positional = (!positional.is_empty()).then_some(positional);
+        let named = (!named.named.is_empty()).then_some(named);
         if positional.is_none() && named.is_none() {
             Ok(None)
         } else {
@@ -98,3 +216,156 @@ pub fn parse_command_tail(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{hir::NamedValue, parse, syntax_shape::SyntaxShape};
+
+    fn signature() -> Signature {
+        Signature::build("cmd")
+            .optional("path", SyntaxShape::Path, "")
+            .switch("all", "", Some('a'))
+            .switch("long", "", Some('l'))
+            .named("depth", SyntaxShape::Int, "", Some('d'))
+    }
+
+    /// A flag's name and the text of its value, or whether the switch is on
+    type Flag<'a> = (String, &'a str);
+
+    /// The positionals and flags that `source`, a call of `cmd`, was parsed into. Flags are listed
+    /// by name, with the text of their value or whether the switch is on.
+    fn call(source: &str) -> Result<(Vec<&str>, Vec<Flag<'_>>), ShellError> {
+        let tokens = parse(source)?.item;
+        let span = tokens[0].span;
+        let parsed =
+            parse_command_tail(&signature(), &mut tokens.into_iter().skip(1), span, source)?;
+        let (positional, named) = parsed.unwrap_or_default();
+        let positional = positional
+            .unwrap_or_default()
+            .iter()
+            .map(|token| token.span.slice(source))
+            .collect();
+        let named = named
+            .map(|named| {
+                named
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = match value {
+                            NamedValue::PresentSwitch(_) => "on",
+                            NamedValue::AbsentSwitch => "off",
+                            NamedValue::AbsentValue => "",
+                            NamedValue::Value(token) => token.span.slice(source),
+                        };
+                        (name.clone(), value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok((positional, named))
+    }
+
+    fn named(source: &str) -> Vec<Flag<'_>> {
+        call(source).unwrap().1
+    }
+
+    fn flag(name: &str, value: &'static str) -> Flag<'static> {
+        (String::from(name), value)
+    }
+
+    /// The text of `source` that the error of parsing it points at
+    fn error_at(source: &str) -> &str {
+        call(source).unwrap_err().span().unwrap().slice(source)
+    }
+
+    #[test]
+    fn short_flags() {
+        assert_eq!(named("cmd -a"), [flag("all", "on")]);
+        assert_eq!(named("cmd -la"), [flag("long", "on"), flag("all", "on")]);
+        assert_eq!(named("cmd -ad 3"), [flag("all", "on"), flag("depth", "3")]);
+        assert_eq!(error_at("cmd -da 3"), "d");
+    }
+
+    #[test]
+    fn long_flags() {
+        assert_eq!(named("cmd --long"), [flag("long", "on")]);
+        assert_eq!(named("cmd --depth 3"), [flag("depth", "3")]);
+        assert_eq!(named("cmd --depth=3"), [flag("depth", "3")]);
+        assert_eq!(named("cmd --all=true"), [flag("all", "on")]);
+        assert_eq!(named("cmd --all=false"), [flag("all", "off")]);
+        assert_eq!(error_at("cmd --all=maybe"), "maybe");
+    }
+
+    #[test]
+    fn flags_and_positionals_mix() {
+        let (positional, named) = call("cmd -a src --depth=1").unwrap();
+        assert_eq!(positional, ["src"]);
+        assert_eq!(named, [flag("all", "on"), flag("depth", "1")]);
+        // A dash followed by digits is a word.
+        assert_eq!(call("cmd -5").unwrap().0, ["-5"]);
+    }
+
+    /// The reason given by the error of parsing `source`
+    fn reason(source: &str) -> String {
+        match call(source).unwrap_err().error {
+            ProximateShellError::ParseError(_, reason) => reason.unwrap_or_default(),
+            error => panic!("expected a parse error, got {:?}", error),
+        }
+    }
+
+    #[test]
+    fn unknown_flags_suggest_the_closest_flag() {
+        assert_eq!(
+            reason("cmd --lang"),
+            "cmd has no flag --lang, did you mean --long?"
+        );
+        assert_eq!(
+            reason("cmd --dept=1"),
+            "cmd has no flag --dept, did you mean --depth?"
+        );
+        assert_eq!(reason("cmd --colour"), "cmd has no flag --colour");
+        assert_eq!(reason("cmd -x"), "cmd has no flag -x");
+        assert_eq!(error_at("cmd -ax"), "x");
+    }
+
+    #[test]
+    fn long_flags_typed_with_one_dash_are_suggested() {
+        assert_eq!(
+            reason("cmd -long"),
+            "cmd has no flag -o, did you mean --long?"
+        );
+        assert_eq!(error_at("cmd -long"), "o");
+    }
+
+    #[test]
+    fn extra_positionals() {
+        assert_eq!(reason("cmd a b"), "cmd takes at most 1 positional argument");
+        assert_eq!(error_at("cmd a b"), "b");
+        let source = "pwd x";
+        let tokens = parse(source).unwrap().item;
+        let err = parse_command_tail(
+            &Signature::build("pwd"),
+            &mut tokens.into_iter().skip(1),
+            Span::new(0, 3),
+            source,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.error,
+            ProximateShellError::ParseError(
+                Span::new(4, 5),
+                Some(String::from("pwd takes no positional arguments"))
+            )
+        );
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("long", "long"), 0);
+        assert_eq!(edit_distance("lnog", "long"), 2);
+        assert_eq!(edit_distance("dept", "depth"), 1);
+        assert_eq!(edit_distance("", "all"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
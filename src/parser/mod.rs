@@ -1,16 +1,17 @@
 use self::{
     span::{Span, Spanned, SpannedItem},
-    token::{SpannedToken, Token},
+    token::{Redirection, SpannedToken, Token},
 };
-use crate::ShellError;
-use alloc::vec::Vec;
+use crate::error::{ProximateShellError, ShellError};
+use alloc::{boxed::Box, string::String, vec::Vec};
 use enumflags2::{bitflags, BitFlags};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while1},
-    character::complete::{char, none_of, space1},
-    combinator::opt,
-    multi::{many0_count, many1},
+    bytes::complete::{tag, take_while, take_while1},
+    character::complete::{anychar, char, multispace0, multispace1, none_of, space0, space1},
+    combinator::{opt, recognize, value},
+    multi::{many0, many0_count, many1, many1_count},
+    sequence::preceded,
     IResult, InputIter, Slice,
 };
 use nom_locate::LocatedSpan;
@@ -21,20 +22,102 @@ pub mod span;
 pub mod syntax_shape;
 pub mod token;
 
+pub use self::command::classified::{classify_pipeline, classify_pipeline_with_aliases};
+
 pub type NomSpan<'a> = LocatedSpan<&'a str>;
 
+/// Splits `input` into tokens. When the input stops in the middle of something, like a quote or a
+/// bracket that is never closed, a backslash that continues the last line or a `|` with nothing
+/// after it, the error is `IncompleteInput` and the input can be completed with more lines.
 pub fn parse(input: &str) -> Result<Spanned<Vec<SpannedToken>>, ShellError> {
-    match token_list(NomSpan::new(input)) {
+    if input.ends_with('\\') {
+        return Err(ProximateShellError::IncompleteInput(
+            Span::for_char(input.len() - 1),
+            String::from("the line continues"),
+        )
+        .start());
+    }
+    match spaced_token_list(NomSpan::new(input)) {
+        Ok((rest, _)) if !rest.fragment().is_empty() => {
+            let offset = rest.location_offset();
+            if let Ok((after, kind)) = redirection(rest) {
+                return Err(ProximateShellError::ParseError(
+                    Span::new(offset, after.location_offset()),
+                    Some(format!("expected a file after {}", kind.operator())),
+                )
+                .start());
+            }
+            if rest.fragment().trim_end() == "|" {
+                return Err(ProximateShellError::IncompleteInput(
+                    Span::for_char(offset),
+                    String::from("expected a command after |"),
+                )
+                .start());
+            }
+            Err(match unclosed(rest.fragment()) {
+                Some((position, reason)) => ProximateShellError::IncompleteInput(
+                    Span::for_char(offset + position),
+                    String::from(reason),
+                ),
+                None => ProximateShellError::ParseError(
+                    Span::for_char(offset),
+                    Some(String::from("unexpected character")),
+                ),
+            }
+            .start())
+        }
         Ok((_rest, val)) => Ok(val),
         Err(err) => Err(ShellError::parse_error(err)),
     }
 }
 
+/// Finds the innermost quote or bracket of `text` that is still open at its end, returning its
+/// position and what is missing. Text where a bracket is closed by the wrong one has nothing that
+/// more input could fix.
+fn unclosed(text: &str) -> Option<(usize, &'static str)> {
+    let mut open: Vec<(usize, char)> = vec![];
+    let mut chars = text.char_indices().peekable();
+    let mut previous = None;
+    while let Some((position, c)) = chars.next() {
+        match (open.last(), c) {
+            (Some((_, '"')), '\\') => {
+                chars.next();
+            }
+            (Some((_, q)), c) if (*q == '"' || *q == '\'') && c == *q => {
+                open.pop();
+            }
+            (Some((_, q)), _) if *q == '"' || *q == '\'' => {}
+            (_, '"') | (_, '\'') | (_, '(') | (_, '[') | (_, '{') => open.push((position, c)),
+            (_, ')') | (_, ']') | (_, '}') => match open.pop() {
+                Some((_, '(')) if c == ')' => {}
+                Some((_, '[')) if c == ']' => {}
+                Some((_, '{')) if c == '}' => {}
+                _ => return None,
+            },
+            (_, '#') if previous.is_none_or(char::is_whitespace) => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            }
+            _ => {}
+        }
+        previous = Some(c);
+    }
+
+    open.last().map(|(position, c)| {
+        let reason = match c {
+            '"' | '\'' => "unterminated string",
+            '(' => "unclosed (",
+            '[' => "unclosed [",
+            _ => "unclosed {",
+        };
+        (*position, reason)
+    })
+}
+
 pub fn dq_string(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
     let start = input.location_offset();
     let (input, _) = char('"')(input)?;
     let start1 = input.location_offset();
-    let (input, _) = many0_count(none_of("\""))(input)?;
+    let (input, _) = many0_count(alt((escape, none_of("\\\""))))(input)?;
     let end1 = input.location_offset();
     let (input, _) = char('"')(input)?;
     let end = input.location_offset();
@@ -65,6 +148,94 @@ pub fn string(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
     alt((sq_string, dq_string))(input)
 }
 
+/// A backslash and the character after it, which are checked when the string is unescaped
+fn escape(input: NomSpan) -> IResult<NomSpan, char> {
+    preceded(char('\\'), anychar)(input)
+}
+
+/// A `$"..."` string, made of text and the `(...)` invocations whose output is spliced into it
+pub fn interpolation(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let start = input.location_offset();
+    let (input, _) = tag("$\"")(input)?;
+    let (input, parts) = many0(alt((invocation, interpolation_text)))(input)?;
+    let (input, _) = char('"')(input)?;
+    let end = input.location_offset();
+
+    Ok((
+        input,
+        Token::Interpolation(parts).spanned(Span::new(start, end)),
+    ))
+}
+
+fn interpolation_text(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let start = input.location_offset();
+    let (input, _) = many1_count(alt((escape, none_of("\\\"("))))(input)?;
+    let span = Span::new(start, input.location_offset());
+
+    Ok((input, Token::String(span).spanned(span)))
+}
+
+/// The text of a quoted string token whose contents are at `contents`. Double quotes process
+/// backslash escapes, single quotes keep everything as it is.
+pub fn unquote(string: &SpannedToken, contents: Span, source: &str) -> Result<String, ShellError> {
+    if string.span.slice(source).starts_with('"') {
+        unescape(contents, source)
+    } else {
+        Ok(contents.string(source))
+    }
+}
+
+/// Replaces the escapes in `span` with the characters they stand for: `\"`, `\\`, `\n`, `\r`,
+/// `\t`, `\(` and `\u{..}` with up to six hex digits, while a backslash at the end of a line joins
+/// it to the next. Anything else after a backslash is an error at that escape.
+pub fn unescape(span: Span, source: &str) -> Result<String, ShellError> {
+    let text = span.slice(source);
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            // A line continuation, which leaves nothing behind
+            Some((_, '\n')) => continue,
+            Some((_, '"')) => Some('"'),
+            Some((_, '\\')) => Some('\\'),
+            Some((_, 'n')) => Some('\n'),
+            Some((_, 'r')) => Some('\r'),
+            Some((_, 't')) => Some('\t'),
+            Some((_, '(')) => Some('('),
+            Some((_, 'u')) if chars.next_if(|(_, c)| *c == '{').is_some() => {
+                let mut digits = String::new();
+                while let Some((_, digit)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+                    digits.push(digit);
+                }
+                chars
+                    .next_if(|(_, c)| *c == '}')
+                    .filter(|_| (1..=6).contains(&digits.len()))
+                    .and_then(|_| u32::from_str_radix(&digits, 16).ok())
+                    .and_then(char::from_u32)
+            }
+            _ => None,
+        };
+        match escaped {
+            Some(escaped) => out.push(escaped),
+            None => {
+                let end = chars.peek().map_or(text.len(), |(end, _)| *end);
+                let start = span.start() + offset;
+                return Err(ProximateShellError::ParseError(
+                    Span::new(start, span.start() + end),
+                    Some(String::from("invalid escape")),
+                )
+                .start());
+            }
+        }
+    }
+
+    Ok(out)
+}
+
 pub fn separator(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
     let left = input.location_offset();
     let (input, _) = alt((tag(";"), tag("\n")))(input)?;
@@ -73,9 +244,63 @@ pub fn separator(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
     Ok((input, Token::Separator.spanned(Span::new(left, right))))
 }
 
+/// `&&` or `||` between two pipelines
+pub fn chain(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let left = input.location_offset();
+    let (input, operator) = alt((tag("&&"), tag("||")))(input)?;
+    let right = input.location_offset();
+    let token = match *operator.fragment() {
+        "&&" => Token::And,
+        _ => Token::Or,
+    };
+
+    Ok((input, token.spanned(Span::new(left, right))))
+}
+
+/// `&` after a pipeline. It is tried after `chain`, which takes `&&`.
+pub fn background(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let left = input.location_offset();
+    let (input, _) = tag("&")(input)?;
+    let right = input.location_offset();
+
+    Ok((input, Token::Background.spanned(Span::new(left, right))))
+}
+
+pub fn pipe(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let left = input.location_offset();
+    let (input, _) = tag("|")(input)?;
+    let right = input.location_offset();
+
+    Ok((input, Token::Pipe.spanned(Span::new(left, right))))
+}
+
+/// Spaces and tabs, along with backslashes that continue the line
 pub fn whitespace(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
     let left = input.location_offset();
-    let (input, _) = space1(input)?;
+    let (input, _) = many1_count(alt((space1, tag("\\\n"))))(input)?;
+    let right = input.location_offset();
+
+    Ok((input, Token::Whitespace.spanned(Span::new(left, right))))
+}
+
+/// A `#` comment, running to the end of the line. It is whitespace to everything after the
+/// tokenizer.
+pub fn comment(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    // Only a `#` at the start of a word begins a comment.
+    let column = input.get_column() - 1;
+    if let Some(before) = input.get_line_beginning()[..column].last() {
+        if !before.is_ascii_whitespace()
+            && !matches!(before, b';' | b'|' | b'&' | b'(' | b'[' | b'{')
+        {
+            return Err(nom::Err::Error(nom::error::make_error(
+                input,
+                nom::error::ErrorKind::Char,
+            )));
+        }
+    }
+    let left = input.location_offset();
+    let (input, _) = char('#')(input)?;
+    let (input, _) = take_while(|c| c != '\n')(input)?;
     let right = input.location_offset();
 
     Ok((input, Token::Whitespace.spanned(Span::new(left, right))))
@@ -83,7 +308,16 @@ pub fn whitespace(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
 
 #[inline]
 pub fn any_space(input: NomSpan) -> IResult<NomSpan, Vec<SpannedToken>> {
-    let (input, tokens) = many1(alt((whitespace, separator)))(input)?;
+    let (input, tokens) = many1(alt((whitespace, comment, separator, chain, background)))(input)?;
+
+    Ok((input, tokens))
+}
+
+#[inline]
+pub fn between_nodes(input: NomSpan) -> IResult<NomSpan, Vec<SpannedToken>> {
+    let (input, tokens) = many1(alt((
+        whitespace, comment, separator, chain, background, pipe,
+    )))(input)?;
 
     Ok((input, tokens))
 }
@@ -120,9 +354,11 @@ fn word<'a, T, U, V>(
     }
 }
 
+/// One character that satisfies `cond`. A backslash that ends the line continues it instead, so it
+/// never becomes part of a word.
 pub fn matches(cond: fn(char) -> bool) -> impl Fn(NomSpan) -> IResult<NomSpan, NomSpan> + Copy {
     move |input: NomSpan| match input.iter_elements().next() {
-        Option::Some(c) if cond(c) => {
+        Option::Some(c) if cond(c) && !input.fragment().starts_with("\\\n") => {
             let len_utf8 = c.len_utf8();
             Ok((input.slice(len_utf8..), input.slice(0..len_utf8)))
         }
@@ -233,9 +469,8 @@ fn after_sep_file(input: NomSpan) -> IResult<NomSpan, BitFlags<SawSpecial>> {
 
     let start = input.location_offset();
     let original_input = input;
-    let input = input;
 
-    let (input, _) = take_while1(after_sep_char)(input)?;
+    let (input, _) = recognize(many1_count(matches(after_sep_char)))(input)?;
 
     let slice = original_input.slice(0..input.location_offset() - start);
 
@@ -255,22 +490,231 @@ pub fn start_filename(input: NomSpan) -> IResult<NomSpan, NomSpan> {
 pub fn flag(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
     let start = input.location_offset();
     let (input, _) = tag("--")(input)?;
+    if let Ok((input, (name, value))) = flag_assignment(input) {
+        let end = input.location_offset();
+        return Ok((
+            input,
+            Token::FlagWithValue(name, Box::new(value)).spanned(Span::new(start, end)),
+        ));
+    }
     let (input, bare) = filename(input)?;
     let end = input.location_offset();
 
     Ok((input, Token::Flag(bare.span).spanned(Span::new(start, end))))
 }
 
+/// The `name=value` after the dashes of a flag
+fn flag_assignment(input: NomSpan) -> IResult<NomSpan, (Span, SpannedToken)> {
+    let (input, name) = take_while1(is_flag_name_char)(input)?;
+    let (input, _) = char('=')(input)?;
+    let (input, value) = alt((
+        string,
+        invocation,
+        variable,
+        list,
+        record,
+        filename,
+        pattern,
+        external_word,
+    ))(input)?;
+
+    Ok((input, (Span::from(name), value)))
+}
+
+/// A `-abc` cluster of single character flags. Anything else after the dash, like the digits of
+/// `-5`, makes it an ordinary word.
+pub fn short_flag(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let start = input.location_offset();
+    let (input, _) = char('-')(input)?;
+    let (input, flags) = take_while1(char::is_alphabetic)(input)?;
+    if let Some(next_char) = input.fragment().chars().next() {
+        if is_external_word_char(next_char) {
+            return Err(nom::Err::Error(nom::error::make_error(
+                input,
+                nom::error::ErrorKind::TakeWhile1,
+            )));
+        }
+    }
+    let end = input.location_offset();
+
+    Ok((
+        input,
+        Token::ShortFlag(Span::from(flags)).spanned(Span::new(start, end)),
+    ))
+}
+
+pub fn invocation(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let start = input.location_offset();
+    let (input, _) = char('(')(input)?;
+    let (input, tokens) = spaced_token_list(input)?;
+    let (input, _) = char(')')(input)?;
+    let end = input.location_offset();
+
+    Ok((
+        input,
+        Token::Invocation(tokens.item).spanned(Span::new(start, end)),
+    ))
+}
+
+pub fn variable(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let start = input.location_offset();
+    let (input, _) = char('$')(input)?;
+    let (input, name) = take_while1(is_variable_char)(input)?;
+    let end = input.location_offset();
+
+    Ok((
+        input,
+        Token::Variable(Span::from(name)).spanned(Span::new(start, end)),
+    ))
+}
+
+pub fn list(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let start = input.location_offset();
+    let (input, _) = char('[')(input)?;
+    let (input, _) = item_separators(input)?;
+    let (input, items) = many0(terminated_item(item))(input)?;
+    let (input, _) = char(']')(input)?;
+    let end = input.location_offset();
+
+    Ok((input, Token::List(items).spanned(Span::new(start, end))))
+}
+
+pub fn record(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let start = input.location_offset();
+    let (input, _) = char('{')(input)?;
+    let (input, _) = item_separators(input)?;
+    let (input, entries) = many0(terminated_item(record_entry))(input)?;
+    let (input, _) = char('}')(input)?;
+    let end = input.location_offset();
+
+    Ok((input, Token::Record(entries).spanned(Span::new(start, end))))
+}
+
+/// A `{ ... }` block of pipelines, which commands like `if` run. Braces that hold `name: value`
+/// entries, or nothing at all, are taken by `record` first.
+pub fn block(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let start = input.location_offset();
+    let (input, _) = char('{')(input)?;
+    let (input, tokens) = spaced_token_list(input)?;
+    let (input, _) = char('}')(input)?;
+    let end = input.location_offset();
+
+    Ok((
+        input,
+        Token::Block(tokens.item).spanned(Span::new(start, end)),
+    ))
+}
+
+fn record_entry(input: NomSpan) -> IResult<NomSpan, (SpannedToken, SpannedToken)> {
+    let (input, key) = alt((string, record_key))(input)?;
+    let (input, _) = space0(input)?;
+    let (input, _) = char(':')(input)?;
+    let (input, _) = multispace0(input)?;
+    let (input, value) = item(input)?;
+
+    Ok((input, (key, value)))
+}
+
+fn record_key(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let start = input.location_offset();
+    let (input, _) = take_while1(is_record_key_char)(input)?;
+    let end = input.location_offset();
+
+    Ok((input, Token::Bare.spanned(Span::new(start, end))))
+}
+
+/// A value inside a list or record literal. Commas separate items there, so unlike a top level
+/// word an item never ends with one.
+fn item(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    alt((
+        string, invocation, variable, list, record, filename, pattern, item_word,
+    ))(input)
+}
+
+fn item_word(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let start = input.location_offset();
+    let (input, _) = many1_count(matches(|c| is_external_word_char(c) && c != ','))(input)?;
+    let end = input.location_offset();
+
+    Ok((input, Token::ExternalWord.spanned(Span::new(start, end))))
+}
+
+/// Whitespace, newlines, comments and commas between the items of a literal
+fn item_separators(input: NomSpan) -> IResult<NomSpan, usize> {
+    many0_count(alt((
+        multispace1,
+        tag(","),
+        tag("\\\n"),
+        recognize(comment),
+    )))(input)
+}
+
+fn terminated_item<'a, T>(
+    parser: impl Fn(NomSpan<'a>) -> IResult<NomSpan<'a>, T>,
+) -> impl FnMut(NomSpan<'a>) -> IResult<NomSpan<'a>, T> {
+    move |input: NomSpan<'a>| {
+        let (input, item) = parser(input)?;
+        let (input, _) = item_separators(input)?;
+
+        Ok((input, item))
+    }
+}
+
 pub fn external_word(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
     let start = input.location_offset();
-    let (input, _) = take_while1(is_external_word_char)(input)?;
+    let (input, _) = many1_count(matches(is_external_word_char))(input)?;
     let end = input.location_offset();
 
     Ok((input, Token::ExternalWord.spanned(Span::new(start, end))))
 }
 
+/// A `<`, `>`, `>>` or `2>` redirection, followed by the file it reads from or writes to
+pub fn redirect(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
+    let start = input.location_offset();
+    let (input, redirection) = redirection(input)?;
+    let (input, _) = opt(whitespace)(input)?;
+    let (input, target) = alt((
+        string,
+        invocation,
+        interpolation,
+        variable,
+        filename,
+        pattern,
+        external_word,
+    ))(input)?;
+    let end = input.location_offset();
+
+    Ok((
+        input,
+        Token::Redirect(redirection, Box::new(target)).spanned(Span::new(start, end)),
+    ))
+}
+
+fn redirection(input: NomSpan) -> IResult<NomSpan, Redirection> {
+    alt((
+        value(Redirection::Append, tag(">>")),
+        value(Redirection::Errors, tag("2>")),
+        value(Redirection::Output, tag(">")),
+        value(Redirection::Input, tag("<")),
+    ))(input)
+}
+
 pub fn node(input: NomSpan) -> IResult<NomSpan, SpannedToken> {
-    let (input, node) = alt((string, flag, filename, pattern, external_word))(input)?;
+    let (input, node) = alt((
+        redirect,
+        string,
+        invocation,
+        interpolation,
+        variable,
+        list,
+        record,
+        block,
+        flag,
+        short_flag,
+        filename,
+        pattern,
+        external_word,
+    ))(input)?;
 
     Ok((input, node))
 }
@@ -301,9 +745,14 @@ pub fn token_list(input: NomSpan) -> IResult<NomSpan, Spanned<Vec<SpannedToken>>
 
         node_list.push(next_node);
 
-        let maybe_space = any_space(after_node_input);
+        let maybe_space = between_nodes(after_node_input);
 
         let after_space_input = match maybe_space {
+            // A redirection can follow a word without a space, as in `echo hi>out.txt`.
+            Err(_) if redirection(after_node_input).is_ok() => {
+                before_space_input = None;
+                after_node_input
+            }
             Err(_) => {
                 next_input = after_node_input;
 
@@ -326,13 +775,17 @@ pub fn token_list(input: NomSpan) -> IResult<NomSpan, Spanned<Vec<SpannedToken>>
 
 pub fn spaced_token_list(input: NomSpan) -> IResult<NomSpan, Spanned<Vec<SpannedToken>>> {
     let start = input.location_offset();
-    let (input, _) = opt(any_space)(input)?;
+    let (input, pre_ws) = opt(any_space)(input)?;
     let (input, items) = token_list(input)?;
     let (input, post_ws) = opt(any_space)(input)?;
     let end = input.location_offset();
 
     let mut out = vec![];
 
+    // Kept for the `&&` and `||` among them
+    if let Some(pre_ws) = pre_ws {
+        out.extend(pre_ws)
+    }
     out.extend(items.item);
     if let Some(post_ws) = post_ws {
         out.extend(post_ws)
@@ -344,12 +797,29 @@ pub fn spaced_token_list(input: NomSpan) -> IResult<NomSpan, Spanned<Vec<Spanned
 #[inline]
 fn is_external_word_char(c: char) -> bool {
     match c {
-        ';' | '|' | '"' | '\'' | '$' | '(' | ')' | '[' | ']' | '{' | '}' | '`' => false,
+        ';' | '|' | '&' | '"' | '\'' | '$' | '(' | ')' | '[' | ']' | '{' | '}' | '`' | '<'
+        | '>' => false,
         other if other.is_whitespace() => false,
         _ => true,
     }
 }
 
+/// The name of a variable, with `.` separating the members looked up in its value
+#[inline]
+fn is_variable_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+#[inline]
+fn is_flag_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-')
+}
+
+#[inline]
+fn is_record_key_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-')
+}
+
 /// These characters appear in globs and not bare words
 #[inline]
 fn is_glob_specific_char(c: char) -> bool {
@@ -403,7 +873,267 @@ fn is_file_char(c: char) -> bool {
         '=' => true,
         '~' => true,
         ':' => true,
+        '.' => true,
         '?' => true,
+        // Only a `#` at the start of a word begins a comment.
+        '#' => true,
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The kinds and text of the tokens of `source`, leaving out whitespace.
+    fn words(source: &str) -> Vec<(&'static str, &str)> {
+        parse(source)
+            .unwrap()
+            .item
+            .iter()
+            .filter(|token| token.item != Token::Whitespace)
+            .map(|token| (token.item.desc(), token.span.slice(source)))
+            .collect()
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            words("let x = $y"),
+            [
+                ("bare", "let"),
+                ("bare", "x"),
+                ("external word", "="),
+                ("variable", "$y")
+            ]
+        );
+        let source = "$env.HOME";
+        let tokens = parse(source).unwrap().item;
+        match &tokens[0].item {
+            Token::Variable(name) => assert_eq!(name.slice(source), "env.HOME"),
+            token => panic!("expected a variable, got {:?}", token),
+        }
+    }
+
+    #[test]
+    fn command_substitutions() {
+        let source = "ls (pwd | first 1) x";
+        assert_eq!(
+            words(source),
+            [
+                ("bare", "ls"),
+                ("command substitution", "(pwd | first 1)"),
+                ("bare", "x")
+            ]
+        );
+        let tokens = parse(source).unwrap().item;
+        match &tokens[2].item {
+            Token::Invocation(inner) => {
+                let inner: Vec<&str> = inner
+                    .iter()
+                    .filter(|token| token.item != Token::Whitespace)
+                    .map(|token| token.span.slice(source))
+                    .collect();
+                assert_eq!(inner, ["pwd", "|", "first", "1"]);
+            }
+            token => panic!("expected a substitution, got {:?}", token),
+        }
+        assert!(parse("ls (pwd").unwrap_err().is_incomplete());
+    }
+
+    #[test]
+    fn list_literals() {
+        let source = "[1, two\n  \"three\" # a comment\n [4]]";
+        let tokens = parse(source).unwrap().item;
+        assert_eq!(tokens.len(), 1);
+        match &tokens[0].item {
+            Token::List(items) => {
+                let items: Vec<(&str, &str)> = items
+                    .iter()
+                    .map(|item| (item.item.desc(), item.span.slice(source)))
+                    .collect();
+                assert_eq!(
+                    items,
+                    [
+                        ("bare", "1"),
+                        ("bare", "two"),
+                        ("string", "\"three\""),
+                        ("list", "[4]")
+                    ]
+                );
+            }
+            token => panic!("expected a list, got {:?}", token),
+        }
+        assert!(parse("[1 2").unwrap_err().is_incomplete());
+    }
+
+    #[test]
+    fn record_literals() {
+        let source = "{a: 1, \"b c\": [x], d:{}}";
+        let tokens = parse(source).unwrap().item;
+        match &tokens[0].item {
+            Token::Record(entries) => {
+                let entries: Vec<(&str, &str)> = entries
+                    .iter()
+                    .map(|(key, value)| (key.span.slice(source), value.span.slice(source)))
+                    .collect();
+                assert_eq!(entries, [("a", "1"), ("\"b c\"", "[x]"), ("d", "{}")]);
+            }
+            token => panic!("expected a record, got {:?}", token),
+        }
+        assert_eq!(words("{}"), [("record", "{}")]);
+        // Braces without `name: value` entries hold a block.
+        assert_eq!(words("{ ls }"), [("block", "{ ls }")]);
+    }
+
+    #[test]
+    fn flags() {
+        assert_eq!(
+            words("ls -la --all --depth=2 -5"),
+            [
+                ("bare", "ls"),
+                ("short flag", "-la"),
+                ("flag", "--all"),
+                ("flag", "--depth=2"),
+                ("bare", "-5")
+            ]
+        );
+        let source = "x --name=\"a b\"";
+        match &parse(source).unwrap().item[2].item {
+            Token::FlagWithValue(name, value) => {
+                assert_eq!(name.slice(source), "name");
+                assert_eq!(value.span.slice(source), "\"a b\"");
+            }
+            token => panic!("expected a flag with a value, got {:?}", token),
+        }
+    }
+
+    /// The text of the quoted string that `source` is
+    fn unquoted(source: &str) -> Result<String, ShellError> {
+        let token = &parse(source)?.item[0];
+        match token.item {
+            Token::String(contents) => unquote(token, contents, source),
+            _ => panic!("expected a string, got {:?}", token),
+        }
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(unquoted(r#""a\"b\\c""#).unwrap(), "a\"b\\c");
+        assert_eq!(unquoted(r#""\n\r\t\(""#).unwrap(), "\n\r\t(");
+        assert_eq!(unquoted(r#""\u{41}\u{1F600}""#).unwrap(), "A\u{1F600}");
+        assert_eq!(unquoted("\"a\\\nb\"").unwrap(), "ab");
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        for (source, escape) in [
+            (r#""a\qb""#, r"\q"),
+            (r#""\u{}""#, r"\u{}"),
+            (r#""\u{1234567}""#, r"\u{1234567}"),
+            (r#""\u{110000}""#, r"\u{110000}"),
+        ] {
+            let err = unquoted(source).unwrap_err();
+            assert_eq!(err.span().unwrap().slice(source), escape, "{}", source);
+        }
+    }
+
+    #[test]
+    fn single_quotes_are_literal() {
+        assert_eq!(unquoted(r"'a\nb\q'").unwrap(), r"a\nb\q");
+        assert_eq!(unquoted(r#"'say "hi"'"#).unwrap(), r#"say "hi""#);
+    }
+
+    #[test]
+    fn interpolations() {
+        let source = r#"echo $"a \" (pwd | length) b""#;
+        let tokens = parse(source).unwrap().item;
+        let parts = match &tokens[2].item {
+            Token::Interpolation(parts) => parts,
+            token => panic!("expected an interpolation, got {:?}", token),
+        };
+        let parts: Vec<_> = parts
+            .iter()
+            .map(|part| (part.item.desc(), part.span.slice(source)))
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                ("string", r#"a \" "#),
+                ("command substitution", "(pwd | length)"),
+                ("string", " b")
+            ]
+        );
+        // An escaped parenthesis is text.
+        assert_eq!(
+            words(r#"$"\(x)""#),
+            [("string interpolation", r#"$"\(x)""#)]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(
+            words("echo foo#bar # a note"),
+            [("bare", "echo"), ("bare", "foo#bar")]
+        );
+        assert_eq!(
+            words("ls;# \"a note\"\npwd"),
+            [
+                ("bare", "ls"),
+                ("separator", ";"),
+                ("separator", "\n"),
+                ("bare", "pwd")
+            ]
+        );
+        assert_eq!(
+            words("ls http://a/b#frag"),
+            [("bare", "ls"), ("bare", "http://a/b#frag")]
+        );
+        assert_eq!(words("# only a note"), []);
+    }
+
+    #[test]
+    fn line_continuations() {
+        assert_eq!(
+            words("echo a \\\n  b"),
+            [("bare", "echo"), ("bare", "a"), ("bare", "b")]
+        );
+        // A continuation is never part of a word, so it splits one.
+        assert_eq!(
+            words("echo a\\\nb"),
+            [("bare", "echo"), ("bare", "a"), ("bare", "b")]
+        );
+    }
+
+    /// What is missing from `source` when more lines could complete it
+    fn incomplete(source: &str) -> Option<(usize, String)> {
+        match parse(source).err()?.error {
+            ProximateShellError::IncompleteInput(span, reason) => Some((span.start(), reason)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn incomplete_input() {
+        let missing = |position, reason: &str| Some((position, String::from(reason)));
+        assert_eq!(incomplete("echo a \\"), missing(7, "the line continues"));
+        assert_eq!(
+            incomplete("echo \"a # b"),
+            missing(5, "unterminated string")
+        );
+        assert_eq!(incomplete("echo 'a"), missing(5, "unterminated string"));
+        assert_eq!(incomplete("ls (pwd"), missing(3, "unclosed ("));
+        assert_eq!(incomplete("[1 (2"), missing(3, "unclosed ("));
+        assert_eq!(incomplete("if $x {"), missing(6, "unclosed {"));
+        assert_eq!(
+            incomplete("ls | "),
+            missing(3, "expected a command after |")
+        );
+        // A quote in a comment opens nothing.
+        assert!(parse("echo # don't").is_ok());
+        // Neither does a bracket closed by the wrong one, which no more input can fix.
+        assert!(parse("echo (a]").is_err());
+        assert_eq!(incomplete("echo (a]"), None);
+    }
+}
@@ -69,6 +69,11 @@ impl Span {
         Span { start, end }
     }
 
+    /// The same span, `offset` bytes further towards the end
+    pub fn shift(&self, offset: usize) -> Span {
+        Span::new(self.start + offset, self.end + offset)
+    }
+
     pub fn for_char(pos: usize) -> Span {
         Span {
             start: pos,
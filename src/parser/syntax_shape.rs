@@ -16,6 +16,25 @@ pub enum SyntaxShape {
     Path,
     /// A glob pattern is allowed, eg `foo*`
     Pattern,
+    /// Only a `{ ... }` block is allowed, which the command runs itself
+    Block,
+}
+
+impl SyntaxShape {
+    /// Finds the shape that a parameter of `def` is annotated with, by the name it is displayed
+    /// with. `int` is also accepted for integers.
+    pub fn from_name(name: &str) -> Option<SyntaxShape> {
+        Some(match name {
+            "any" => SyntaxShape::Any,
+            "string" => SyntaxShape::String,
+            "number" => SyntaxShape::Number,
+            "int" | "integer" => SyntaxShape::Int,
+            "path" => SyntaxShape::Path,
+            "pattern" => SyntaxShape::Pattern,
+            "block" => SyntaxShape::Block,
+            _ => return None,
+        })
+    }
 }
 
 impl fmt::Display for SyntaxShape {
@@ -30,6 +49,7 @@ impl fmt::Display for SyntaxShape {
                 SyntaxShape::Int => "integer",
                 SyntaxShape::Path => "path",
                 SyntaxShape::Pattern => "pattern",
+                SyntaxShape::Block => "block",
             }
         )
     }
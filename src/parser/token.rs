@@ -1,4 +1,5 @@
 use super::span::{Span, Spanned};
+use alloc::{boxed::Box, vec::Vec};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Deserialize, Serialize)]
@@ -6,10 +7,59 @@ pub enum Token {
     String(Span),
     Bare,
     Flag(Span),
+    /// A `-abc` cluster of single character flags, the span covers the characters after the dash
+    ShortFlag(Span),
+    /// A `--name=value` flag, the span covers the name
+    FlagWithValue(Span, Box<SpannedToken>),
     Whitespace,
     Separator,
+    Pipe,
+    /// `&&`, running the next pipeline only if the one before it succeeded
+    And,
+    /// `||`, running the next pipeline only if the one before it failed
+    Or,
+    /// `&` after a pipeline, running it as a background job
+    Background,
     GlobPattern,
     ExternalWord,
+    /// A `$name` reference, the span covers the name and any `.member`s after it
+    Variable(Span),
+    /// A `(...)` command substitution, holding the tokens of the pipeline inside the parentheses
+    Invocation(Vec<SpannedToken>),
+    /// A `$"..."` string, holding its text as strings and its `(...)` parts as invocations
+    Interpolation(Vec<SpannedToken>),
+    /// A `[a b c]` literal, holding its items
+    List(Vec<SpannedToken>),
+    /// A `{name: value}` literal, holding its keys and values in order
+    Record(Vec<(SpannedToken, SpannedToken)>),
+    /// A `{ ... }` block, holding the tokens of the pipelines inside the braces
+    Block(Vec<SpannedToken>),
+    /// A `<`, `>`, `>>` or `2>` redirection, holding the file it names
+    Redirect(Redirection, Box<SpannedToken>),
+}
+
+/// Which stream a redirection connects to a file
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Deserialize, Serialize)]
+pub enum Redirection {
+    /// `< file`, read as the input of the first command
+    Input,
+    /// `> file`, replaced by the output of the pipeline
+    Output,
+    /// `>> file`, with the output of the pipeline appended to it
+    Append,
+    /// `2> file`, replaced by the error output of an external command
+    Errors,
+}
+
+impl Redirection {
+    pub fn operator(self) -> &'static str {
+        match self {
+            Redirection::Input => "<",
+            Redirection::Output => ">",
+            Redirection::Append => ">>",
+            Redirection::Errors => "2>",
+        }
+    }
 }
 
 impl Token {
@@ -18,16 +68,67 @@ impl Token {
             Token::String(_) => "string",
             Token::Bare => "bare",
             Token::Flag(_) => "flag",
+            Token::ShortFlag(_) => "short flag",
+            Token::FlagWithValue(..) => "flag",
             Token::Whitespace => "whitespace",
             Token::Separator => "separator",
+            Token::Pipe => "pipe",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::Background => "&",
             Token::GlobPattern => "glob pattern",
             Token::ExternalWord => "external word",
+            Token::Variable(_) => "variable",
+            Token::Invocation(_) => "command substitution",
+            Token::Interpolation(_) => "string interpolation",
+            Token::List(_) => "list",
+            Token::Record(_) => "record",
+            Token::Block(_) => "block",
+            Token::Redirect(..) => "redirection",
         }
     }
 }
 
 pub type SpannedToken = Spanned<Token>;
 
+impl SpannedToken {
+    /// Moves the token, along with the spans and tokens inside it, `offset` bytes towards the end,
+    /// to point into a source that its own source was appended to.
+    pub fn shifted(self, offset: usize) -> SpannedToken {
+        let shift = |tokens: Vec<SpannedToken>| {
+            tokens
+                .into_iter()
+                .map(|token| token.shifted(offset))
+                .collect()
+        };
+        let item = match self.item {
+            Token::String(span) => Token::String(span.shift(offset)),
+            Token::Flag(span) => Token::Flag(span.shift(offset)),
+            Token::ShortFlag(span) => Token::ShortFlag(span.shift(offset)),
+            Token::FlagWithValue(span, value) => {
+                Token::FlagWithValue(span.shift(offset), Box::new(value.shifted(offset)))
+            }
+            Token::Variable(span) => Token::Variable(span.shift(offset)),
+            Token::Invocation(tokens) => Token::Invocation(shift(tokens)),
+            Token::Interpolation(tokens) => Token::Interpolation(shift(tokens)),
+            Token::List(tokens) => Token::List(shift(tokens)),
+            Token::Record(entries) => Token::Record(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.shifted(offset), value.shifted(offset)))
+                    .collect(),
+            ),
+            Token::Block(tokens) => Token::Block(shift(tokens)),
+            Token::Redirect(kind, target) => {
+                Token::Redirect(kind, Box::new(target.shifted(offset)))
+            }
+            item => item,
+        };
+
+        Spanned::new(self.span.shift(offset), item)
+    }
+}
+
 impl From<&SpannedToken> for Span {
     fn from(token: &SpannedToken) -> Span {
         token.span
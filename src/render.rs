@@ -0,0 +1,459 @@
+use crate::{
+    evaluate::{value::Record, Value},
+    shell::Shell,
+};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::time::Duration;
+
+/// How many rows are collected into one table by a `StreamRenderer`.
+const BATCH_SIZE: usize = 100;
+
+/// How long output may pause before the rows a `StreamRenderer` holds back are shown anyway,
+/// so that a slow command doesn't look hung.
+pub const FLUSH_AFTER: Duration = Duration::from_millis(250);
+
+/// The narrowest a column is squeezed to before columns start getting dropped.
+const MIN_COLUMN_WIDTH: usize = 3;
+
+/// The characters tables are drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableStyle {
+    /// Rounded box-drawing characters, for terminals that can show them
+    Unicode,
+    /// Plain `+`, `-` and `|`, for serial consoles and other ASCII-only outputs
+    Ascii,
+}
+
+struct BoxChars {
+    horizontal: char,
+    vertical: char,
+    top: [char; 3],
+    middle: [char; 3],
+    bottom: [char; 3],
+    ellipsis: char,
+}
+
+impl TableStyle {
+    fn chars(self) -> BoxChars {
+        match self {
+            TableStyle::Unicode => BoxChars {
+                horizontal: '─',
+                vertical: '│',
+                top: ['╭', '┬', '╮'],
+                middle: ['├', '┼', '┤'],
+                bottom: ['╰', '┴', '╯'],
+                ellipsis: '…',
+            },
+            TableStyle::Ascii => BoxChars {
+                horizontal: '-',
+                vertical: '|',
+                top: ['+', '+', '+'],
+                middle: ['+', '+', '+'],
+                bottom: ['+', '+', '+'],
+                ellipsis: '~',
+            },
+        }
+    }
+}
+
+/// Turns command output into text: records are drawn as tables with a header row, anything else
+/// is written one value per line.
+#[derive(Debug, Clone, Copy)]
+pub struct Renderer {
+    width: usize,
+    style: TableStyle,
+}
+
+/// Renders a stream one value at a time, for callers that need to do something between values.
+///
+/// Rows are buffered into batches so that their columns line up, everything else is rendered as
+/// soon as it arrives. A batch is rendered early with `flush` when the stream pauses.
+pub struct StreamRenderer {
+    renderer: Renderer,
+    batch: Vec<Value>,
+}
+
+impl StreamRenderer {
+    pub fn new(renderer: Renderer) -> Self {
+        Self {
+            renderer,
+            batch: vec![],
+        }
+    }
+
+    /// Takes the next value, returning the text that is ready to be shown.
+    pub fn push(&mut self, value: Value) -> Option<String> {
+        let is_row = matches!(value, Value::Record(_));
+        let mut text = String::new();
+        if !is_row && !self.batch.is_empty() {
+            text.push_str(&self.renderer.render(&self.batch));
+            self.batch.clear();
+        }
+        self.batch.push(value);
+        if !is_row || self.batch.len() == BATCH_SIZE {
+            text.push_str(&self.renderer.render(&self.batch));
+            self.batch.clear();
+        }
+
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// Renders the rows that are buffered so far, when the stream has paused.
+    pub fn flush(&mut self) -> Option<String> {
+        let text = (!self.batch.is_empty()).then(|| self.renderer.render(&self.batch));
+        self.batch.clear();
+        text
+    }
+
+    /// Renders the rows that are still buffered once the stream has ended.
+    pub fn finish(mut self) -> Option<String> {
+        self.flush()
+    }
+}
+
+struct Cell {
+    text: String,
+    right_align: bool,
+}
+
+impl Renderer {
+    pub fn new(width: usize, style: TableStyle) -> Self {
+        Self { width, style }
+    }
+
+    /// A renderer that fits the terminal of `shell`, drawing tables with box-drawing characters
+    /// only if it can show them.
+    pub fn for_shell(shell: &dyn Shell) -> Self {
+        let style = if shell.supports_unicode() {
+            TableStyle::Unicode
+        } else {
+            TableStyle::Ascii
+        };
+
+        Self::new(shell.terminal_width(), style)
+    }
+
+    /// A renderer for text that doesn't end up on a terminal, like a file that output is redirected
+    /// to: tables are drawn in plain ASCII and nothing is cut to fit a width.
+    pub fn plain() -> Self {
+        Self::new(usize::MAX, TableStyle::Ascii)
+    }
+
+    /// Renders values as they arrive, handing the text to `emit` piece by piece and stopping at
+    /// the first error it returns. See `StreamRenderer` for how the values are batched.
+    pub fn render_stream<E>(
+        &self,
+        values: impl Iterator<Item = Value>,
+        mut emit: impl FnMut(&str) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut stream = StreamRenderer::new(*self);
+        for value in values {
+            if let Some(text) = stream.push(value) {
+                emit(&text)?;
+            }
+        }
+        match stream.finish() {
+            Some(text) => emit(&text),
+            None => Ok(()),
+        }
+    }
+
+    /// Renders a batch of values. Consecutive records share one table, so a stream of rows should
+    /// be handed over in batches rather than one value at a time.
+    pub fn render(&self, values: &[Value]) -> String {
+        let mut out = String::new();
+        let mut rows: Vec<&Record> = vec![];
+        for value in values {
+            match value {
+                Value::Record(record) => {
+                    rows.push(record);
+                    continue;
+                }
+                Value::Table(table) => {
+                    self.flush(&mut rows, &mut out);
+                    self.table(&table.iter().collect::<Vec<_>>(), &mut out);
+                }
+                Value::List(list) => {
+                    self.flush(&mut rows, &mut out);
+                    out.push_str(&self.render(list));
+                }
+                value => {
+                    self.flush(&mut rows, &mut out);
+                    out.push_str(&value.to_string());
+                    out.push('\n');
+                }
+            }
+        }
+        self.flush(&mut rows, &mut out);
+
+        out
+    }
+
+    fn flush(&self, rows: &mut Vec<&Record>, out: &mut String) {
+        if !rows.is_empty() {
+            self.table(rows, out);
+            rows.clear();
+        }
+    }
+
+    fn table(&self, rows: &[&Record], out: &mut String) {
+        let mut columns: Vec<&str> = vec![];
+        for row in rows {
+            for column in row.keys() {
+                if !columns.contains(&column.as_str()) {
+                    columns.push(column);
+                }
+            }
+        }
+        if columns.is_empty() {
+            return;
+        }
+
+        let mut cells: Vec<Vec<Cell>> = rows
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|column| cell(row.get(*column)))
+                    .collect()
+            })
+            .collect();
+        let mut widths: Vec<usize> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].text.chars().count())
+                    .fold(column.chars().count(), usize::max)
+            })
+            .collect();
+        let shown = widths.len() - self.fit(&mut widths);
+
+        let chars = self.style.chars();
+        let mut header: Vec<Cell> = columns
+            .iter()
+            .map(|column| Cell {
+                text: column.to_string(),
+                right_align: false,
+            })
+            .collect();
+        // The columns that didn't fit are replaced by one of ellipses.
+        if shown < columns.len() {
+            widths.push(1);
+            for row in core::iter::once(&mut header).chain(&mut cells) {
+                row.truncate(shown);
+                row.push(Cell {
+                    text: chars.ellipsis.to_string(),
+                    right_align: false,
+                });
+            }
+        }
+        self.border(&widths, chars.top, &chars, out);
+        self.row(&header, &widths, &chars, out);
+        self.border(&widths, chars.middle, &chars, out);
+        for row in &cells {
+            self.row(row, &widths, &chars, out);
+        }
+        self.border(&widths, chars.bottom, &chars, out);
+    }
+
+    /// Shrinks the widest columns until the table fits, dropping columns from the right once
+    /// every column is as narrow as it gets. Returns how many columns were dropped.
+    fn fit(&self, widths: &mut Vec<usize>) -> usize {
+        let columns = widths.len();
+        // Every column takes a border and a space on either side, plus the final border. Once
+        // columns are dropped, a last column a single character wide marks them.
+        let overhead = |shown: usize| 3 * shown + 1 + if shown < columns { 4 } else { 0 };
+        while widths.len() > 1
+            && overhead(widths.len()) + MIN_COLUMN_WIDTH * widths.len() > self.width
+        {
+            widths.pop();
+        }
+        while overhead(widths.len()) + widths.iter().sum::<usize>() > self.width {
+            let (widest, width) = widths
+                .iter()
+                .copied()
+                .enumerate()
+                .max_by_key(|(_, width)| *width)
+                .expect("a table has at least one column");
+            if width <= MIN_COLUMN_WIDTH {
+                break;
+            }
+            widths[widest] -= 1;
+        }
+
+        columns - widths.len()
+    }
+
+    fn border(
+        &self,
+        widths: &[usize],
+        [left, mid, right]: [char; 3],
+        chars: &BoxChars,
+        out: &mut String,
+    ) {
+        out.push(left);
+        for (i, width) in widths.iter().enumerate() {
+            if i > 0 {
+                out.push(mid);
+            }
+            out.extend(core::iter::repeat_n(chars.horizontal, width + 2));
+        }
+        out.push(right);
+        out.push('\n');
+    }
+
+    fn row(&self, cells: &[Cell], widths: &[usize], chars: &BoxChars, out: &mut String) {
+        out.push(chars.vertical);
+        for (cell, width) in cells.iter().zip(widths) {
+            out.push(' ');
+            let text = truncate(&cell.text, *width, chars.ellipsis);
+            let padding = width - text.chars().count();
+            if cell.right_align {
+                out.extend(core::iter::repeat_n(' ', padding));
+                out.push_str(&text);
+            } else {
+                out.push_str(&text);
+                out.extend(core::iter::repeat_n(' ', padding));
+            }
+            out.push(' ');
+            out.push(chars.vertical);
+        }
+        out.push('\n');
+    }
+}
+
+fn cell(value: Option<&Value>) -> Cell {
+    let text = match value {
+        None | Some(Value::Nothing) => String::new(),
+        Some(Value::Record(record)) => format!("[record {} fields]", record.len()),
+        Some(Value::Table(rows)) => format!("[table {} rows]", rows.len()),
+        Some(Value::List(list)) => format!("[list {} items]", list.len()),
+        Some(value) => value.to_string().replace('\n', " "),
+    };
+    let right_align = matches!(value, Some(Value::Int(_)) | Some(Value::Number(_)));
+
+    Cell { text, right_align }
+}
+
+fn truncate(text: &str, width: usize, ellipsis: char) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    } else if width == 0 {
+        return String::new();
+    }
+
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push(ellipsis);
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(name: &str) -> Value {
+        let mut record = Record::new();
+        record.insert(String::from("name"), Value::String(String::from(name)));
+        Value::Record(record)
+    }
+
+    fn wide_row() -> Value {
+        let mut record = Record::new();
+        for column in &["first", "second", "third", "fourth", "fifth"] {
+            record.insert(column.to_string(), Value::String("x".repeat(10)));
+        }
+        Value::Record(record)
+    }
+
+    fn renderer() -> StreamRenderer {
+        StreamRenderer::new(Renderer::new(80, TableStyle::Ascii))
+    }
+
+    #[test]
+    fn rows_are_held_back_until_the_batch_is_full() {
+        let mut renderer = renderer();
+        for i in 1..BATCH_SIZE {
+            assert_eq!(renderer.push(row(&i.to_string())), None);
+        }
+        let text = renderer.push(row("last")).expect("a full batch");
+        assert!(text.contains("| 1 "));
+        assert!(text.contains("| last "));
+        assert_eq!(renderer.finish(), None);
+    }
+
+    #[test]
+    fn other_values_render_the_batch_before_them() {
+        let mut renderer = renderer();
+        assert_eq!(renderer.push(row("a")), None);
+        let text = renderer.push(Value::String(String::from("b"))).unwrap();
+        assert!(text.find("| a ").unwrap() < text.find("b\n").unwrap());
+        assert_eq!(
+            renderer.push(Value::String(String::from("c"))).unwrap(),
+            "c\n"
+        );
+    }
+
+    #[test]
+    fn flush_renders_a_partial_batch() {
+        let mut renderer = renderer();
+        assert_eq!(renderer.flush(), None);
+        renderer.push(row("a"));
+        assert!(renderer.flush().unwrap().contains("| a "));
+        assert_eq!(renderer.flush(), None);
+        renderer.push(row("b"));
+        assert!(renderer.finish().unwrap().contains("| b "));
+    }
+
+    #[test]
+    fn fit_keeps_columns_that_fit() {
+        let renderer = Renderer::new(40, TableStyle::Ascii);
+        let mut widths = vec![10, 10];
+        assert_eq!(renderer.fit(&mut widths), 0);
+        assert_eq!(widths, [10, 10]);
+    }
+
+    #[test]
+    fn fit_shrinks_the_widest_column() {
+        let renderer = Renderer::new(20, TableStyle::Ascii);
+        let mut widths = vec![4, 20];
+        assert_eq!(renderer.fit(&mut widths), 0);
+        assert_eq!(widths, [4, 9]);
+    }
+
+    #[test]
+    fn fit_drops_columns_that_are_too_narrow() {
+        let renderer = Renderer::new(20, TableStyle::Ascii);
+        let mut widths = vec![10, 10, 10, 10];
+        let dropped = renderer.fit(&mut widths);
+        assert_eq!(dropped, 2);
+        // The column of ellipses takes its borders and one character.
+        assert!(3 * widths.len() + 1 + 4 + widths.iter().sum::<usize>() <= 20);
+    }
+
+    #[test]
+    fn dropped_columns_are_marked_with_ellipses() {
+        let text = Renderer::new(24, TableStyle::Ascii).render(&[wide_row(), wide_row()]);
+        for line in text.lines() {
+            assert!(line.chars().count() <= 24, "{:?} is too wide", line);
+        }
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[1].ends_with("| ~ |"));
+        assert!(lines[3].ends_with("| ~ |"));
+        assert!(lines[4].ends_with("| ~ |"));
+        // Three columns are left, next to the one of ellipses.
+        assert_eq!(lines[1].matches('|').count(), 5);
+        assert!(!text.contains("fif"));
+    }
+
+    #[test]
+    fn plain_output_is_never_truncated() {
+        let text = Renderer::plain().render(&[wide_row()]);
+        assert!(text.contains("fifth"));
+        assert!(!text.contains('~'));
+    }
+}
@@ -1,35 +1,233 @@
 use crate::{
+    cli::line_editor::LineEditor,
     commands::{CdArgs, LsArgs, MkDirArgs, RunnableContext},
     error::ShellError,
     evaluate::Value,
+    parser::command::classified::external::ExternalCommand,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
 };
-use alloc::{boxed::Box, string::String, vec::Vec};
-use core::{future::Future, pin::Pin};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::{future::Future, pin::Pin, sync::atomic::AtomicBool, time::Duration};
+use spin::RwLock;
 
 #[cfg(feature = "std")]
 pub mod std;
 #[cfg(feature = "std")]
 pub use self::std::StdShell;
+#[cfg(test)]
+pub(crate) mod test;
+
+/// A file opened by `Shell::open_write`, which is closed once it is dropped.
+pub trait FileWriter: Send {
+    /// Writes `text` at the end of the file.
+    fn write_str(&mut self, text: &str) -> Result<(), ShellError>;
+}
+
+/// The values of a stream handed over by `Shell::watch_stream`, with `None` whenever it pauses
+pub type WatchedStream = Box<dyn Iterator<Item = Option<Value>> + Send>;
+
+/// What an external command hands back to its pipeline.
+#[derive(Debug)]
+pub enum ExternalOutput {
+    /// The process ran attached to the terminal and exited with the given status, `0` meaning success
+    Exited(i32),
+    /// The process is still running, its stdout lines are streamed as they are written and its
+    /// status is set once they end
+    Stream(OutputStream, Process),
+}
+
+/// A process started by `run_external` that is still running when it returns.
+#[derive(Debug, Clone)]
+pub struct Process {
+    pub id: u32,
+    pub exit: ExitCode,
+}
+
+impl Process {
+    #[inline]
+    pub fn is_running(&self) -> bool {
+        self.exit.get().is_none()
+    }
+}
+
+/// What `Shell::signal` does to a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Kill,
+    /// Pauses the process until it is sent `Continue`
+    Stop,
+    Continue,
+}
+
+/// The exit status of a streamed external command, which is only known once its output has ended.
+#[derive(Debug, Clone, Default)]
+pub struct ExitCode(Arc<RwLock<Option<i32>>>);
+
+impl ExitCode {
+    pub fn set(&self, status: i32) {
+        *self.0.write() = Some(status);
+    }
+
+    /// Returns `None` while the process is still running.
+    pub fn get(&self) -> Option<i32> {
+        *self.0.read()
+    }
+}
 
 pub trait Shell: core::fmt::Debug + Send + Sync {
     fn name(&self) -> &str;
 
     fn homedir(&self) -> Option<String>;
 
-    fn readline(&self) -> Pin<Box<dyn Future<Output = String>>>;
+    /// Looks up an environment variable, for `$env.NAME`. Hosts without an environment can keep
+    /// the default, which has none set.
+    fn env_var(&self, _name: &str) -> Option<String> {
+        None
+    }
+
+    /// Waits for the next bytes the user types. An empty result means the input is closed.
+    fn read_input(&self) -> Pin<Box<dyn Future<Output = Vec<u8>> + '_>>;
+
+    /// Shows `prompt` and reads a line without its line ending, or `None` once the input is
+    /// closed.
+    ///
+    /// By default the line is edited with `editor` on top of `read_input`, which expects the input
+    /// not to be echoed or line buffered by the other side.
+    fn readline<'a>(
+        &'a self,
+        prompt: &'a str,
+        editor: &'a mut LineEditor,
+    ) -> Pin<Box<dyn Future<Output = Option<String>> + 'a>> {
+        Box::pin(editor.read_line(self, prompt))
+    }
 
     fn print(&self, s: &str);
 
-    fn ls(&self, args: LsArgs, context: &RunnableContext)
-        -> Result<Option<Vec<Value>>, ShellError>;
+    /// Loads the lines saved by `save_history`, oldest first.
+    ///
+    /// Hosts without storage can keep the default, which starts every session without history.
+    fn load_history(&self) -> BoxFuture<'_, Result<Vec<String>, ShellError>> {
+        Box::pin(core::future::ready(Ok(vec![])))
+    }
+
+    /// Stores the history, so that the next session can pick it up with `load_history`.
+    fn save_history(&self, _entries: Vec<String>) -> BoxFuture<'_, Result<(), ShellError>> {
+        Box::pin(core::future::ready(Ok(())))
+    }
+
+    /// The number of columns output is wrapped to, used to fit tables on screen.
+    fn terminal_width(&self) -> usize {
+        80
+    }
 
-    fn cd(&self, args: CdArgs) -> Result<Option<Vec<Value>>, ShellError>;
+    /// Whether the output can show box-drawing characters. Serial consoles usually can't, so
+    /// tables fall back to plain ASCII unless a host opts in.
+    fn supports_unicode(&self) -> bool {
+        false
+    }
+
+    fn ls(
+        &self,
+        args: LsArgs,
+        context: &RunnableContext,
+    ) -> BoxFuture<'_, Result<OutputStream, ShellError>>;
+
+    fn cd(&self, args: CdArgs) -> BoxFuture<'_, Result<OutputStream, ShellError>>;
 
     // fn cp(&self, args: CopyArgs) -> Result<Option<Vec<Value>>, ShellError>;
-    fn mkdir(&self, args: MkDirArgs) -> Result<Option<Vec<Value>>, ShellError>;
+    fn mkdir(&self, args: MkDirArgs) -> BoxFuture<'_, Result<OutputStream, ShellError>>;
     // fn mv(&self, args: MoveArgs, name: Tag, path: &str) -> Result<OutputStream, ShellError>;
     // fn rm(&self, args: RemoveArgs, name: Tag, path: &str) -> Result<OutputStream, ShellError>;
     fn path(&self) -> String;
     // fn pwd(&self) -> Result<Option<Vec<Value>>, ShellError>;
     // fn set_path(&mut self, path: String);
+
+    /// Opens the file at `path` for a `<` redirection, streaming its lines as strings.
+    ///
+    /// Hosts without files, or that keep them somewhere other than `std::fs`, can back this with
+    /// their own storage. The default has no files to read.
+    fn open_read<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<InputStream, ShellError>> {
+        Box::pin(core::future::ready(Err(ShellError::runtime_error(
+            format!("{}: this shell has no files to read", path),
+        ))))
+    }
+
+    /// Opens the file at `path` for a `>`, `>>` or `2>` redirection, creating it if it doesn't
+    /// exist. Its contents are replaced, unless `append` asks for them to be kept.
+    ///
+    /// Like `open_read`, the default has no files to write to.
+    fn open_write<'a>(
+        &'a self,
+        path: &'a str,
+        _append: bool,
+    ) -> BoxFuture<'a, Result<Box<dyn FileWriter>, ShellError>> {
+        Box::pin(core::future::ready(Err(ShellError::runtime_error(
+            format!("{}: this shell has no files to write", path),
+        ))))
+    }
+
+    /// Lists the paths that start with `partial`, for tab completion. Directories end in a `/`.
+    fn complete_path<'a>(&'a self, _partial: &'a str) -> BoxFuture<'a, Vec<String>> {
+        Box::pin(core::future::ready(vec![]))
+    }
+
+    /// Lists the programs that `run_external` can start, for completing command names.
+    fn executables(&self) -> BoxFuture<'_, Vec<String>> {
+        Box::pin(core::future::ready(vec![]))
+    }
+
+    /// Runs `job` to completion in the background, for pipelines started with `&`.
+    ///
+    /// Hosts without threads or an executor to hand it to can keep the default, which refuses to
+    /// start background jobs.
+    fn spawn_job(&self, _job: BoxFuture<'static, ()>) -> Result<(), ShellError> {
+        Err(ShellError::runtime_error(
+            "this shell can't run jobs in the background",
+        ))
+    }
+
+    /// Hands over the values of `stream` as they arrive, with a `None` each time it goes `timeout`
+    /// without producing one, so that whoever shows them can flush what it holds back.
+    ///
+    /// Hosts that can't wait for a value with a timeout can keep the default, which never reports
+    /// a pause.
+    fn watch_stream(&self, stream: OutputStream, _timeout: Duration) -> WatchedStream {
+        Box::new(stream.map(Some))
+    }
+
+    /// Sends `signal` to `process`, returning once the process has been killed or stopped.
+    ///
+    /// Hosts that can't control their processes can keep the default, which refuses to.
+    fn signal(&self, _process: &Process, _signal: Signal) -> Result<(), ShellError> {
+        Err(ShellError::runtime_error(
+            "this shell can't signal its processes",
+        ))
+    }
+
+    /// Sets `ctrl_c` whenever the user interrupts what runs in the foreground, like a ctrl-c at the
+    /// terminal while a pipeline is running.
+    ///
+    /// Hosts that have no way to interrupt can keep the default, which never does.
+    fn interrupt_on_ctrl_c(&self, _ctrl_c: Arc<AtomicBool>) {}
+
+    /// Runs `command` as a separate program, feeding `input` to it as text. The last command of a
+    /// pipeline should be attached to the terminal and waited for, the others have their output
+    /// streamed to the next stage. When `errors` is given, the error output of the program is
+    /// written to it instead of the terminal.
+    ///
+    /// Hosts without a process model can keep the default, which treats every external command as
+    /// not found.
+    fn run_external(
+        &self,
+        command: &ExternalCommand,
+        _input: Option<InputStream>,
+        _is_last: bool,
+        _errors: Option<Box<dyn FileWriter>>,
+    ) -> BoxFuture<'_, Result<ExternalOutput, ShellError>> {
+        Box::pin(core::future::ready(Err(ShellError::labeled_error(
+            format!("{}: command not found", command.name),
+            command.name_span,
+        ))))
+    }
 }
@@ -1,11 +1,32 @@
 use crate::{
+    cli::line_editor::LineEditor,
     commands::{CdArgs, LsArgs, MkDirArgs, RunnableContext},
     error::ShellError,
-    evaluate::Value,
+    evaluate::{value::Record, Value},
+    parser::command::classified::external::ExternalCommand,
+    stream::{InputStream, OutputStream},
+    BoxFuture,
+};
+use std::{
+    env,
+    env::current_dir,
+    ffi::OsStr,
+    fs,
+    future::{self, Future},
+    io::{self, stdout, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::{self, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, OnceLock,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use std::{env, env::current_dir, ffi::OsStr, future::Future, io::{self, Write, stdout}, path::PathBuf, pin::Pin, sync::atomic::Ordering};
 
-use super::Shell;
+use super::{ExitCode, ExternalOutput, FileWriter, Process, Shell, Signal, WatchedStream};
 
 #[derive(Debug, Clone, Default)]
 pub struct StdShell {}
@@ -26,11 +47,38 @@ impl Shell for StdShell {
         dirs::home_dir().map(|p| p.to_string_lossy().to_string())
     }
 
-    fn readline(&self) -> Pin<Box<dyn Future<Output = String>>> {
-        let future = async {
+    fn env_var(&self, name: &str) -> Option<String> {
+        env::var(name).ok()
+    }
+
+    fn read_input(&self) -> Pin<Box<dyn Future<Output = Vec<u8>> + '_>> {
+        let mut buf = [0; 64];
+        let read = io::stdin().lock().read(&mut buf).unwrap_or(0);
+
+        Box::pin(future::ready(buf[..read].to_vec()))
+    }
+
+    fn readline<'a>(
+        &'a self,
+        prompt: &'a str,
+        editor: &'a mut LineEditor,
+    ) -> Pin<Box<dyn Future<Output = Option<String>> + 'a>> {
+        let future = async move {
+            if let Some(_raw_mode) = RawMode::enable() {
+                return editor.read_line(self, prompt).await;
+            }
+
+            // Not a terminal, so there is nothing to edit and the input isn't echoed either.
+            self.print(prompt);
             let mut s = String::new();
-            io::stdin().read_line(&mut s).unwrap();
-            s
+            match io::stdin().read_line(&mut s) {
+                Ok(0) | Err(_) => None,
+                Ok(_) => {
+                    let len = s.trim_end_matches(&['\r', '\n'][..]).len();
+                    s.truncate(len);
+                    Some(s)
+                }
+            }
         };
 
         Box::pin(future)
@@ -41,16 +89,236 @@ impl Shell for StdShell {
         stdout().flush().unwrap();
     }
 
+    fn load_history(&self) -> BoxFuture<'_, Result<Vec<String>, ShellError>> {
+        Box::pin(future::ready(self.read_history()))
+    }
+
+    fn save_history(&self, entries: Vec<String>) -> BoxFuture<'_, Result<(), ShellError>> {
+        Box::pin(future::ready(self.write_history(entries)))
+    }
+
+    fn terminal_width(&self) -> usize {
+        terminal_size()
+            .or_else(|| env::var("COLUMNS").ok()?.parse().ok())
+            .unwrap_or(80)
+    }
+
+    fn supports_unicode(&self) -> bool {
+        ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+            .map(|locale| {
+                let locale = locale.to_ascii_uppercase();
+                locale.contains("UTF-8") || locale.contains("UTF8")
+            })
+            .unwrap_or(false)
+    }
+
     fn ls(
         &self,
-        LsArgs { path }: LsArgs,
+        args: LsArgs,
+        context: &RunnableContext,
+    ) -> BoxFuture<'_, Result<OutputStream, ShellError>> {
+        Box::pin(future::ready(self.list(args, context)))
+    }
+
+    fn cd(&self, args: CdArgs) -> BoxFuture<'_, Result<OutputStream, ShellError>> {
+        Box::pin(future::ready(self.change_dir(args)))
+    }
+
+    fn mkdir(&self, args: MkDirArgs) -> BoxFuture<'_, Result<OutputStream, ShellError>> {
+        Box::pin(future::ready(self.make_dirs(args)))
+    }
+
+    fn path(&self) -> String {
+        current_dir()
+            .expect("can't get current directory")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn open_read<'a>(&'a self, path: &'a str) -> BoxFuture<'a, Result<InputStream, ShellError>> {
+        Box::pin(future::ready(self.read_file(path)))
+    }
+
+    fn open_write<'a>(
+        &'a self,
+        path: &'a str,
+        append: bool,
+    ) -> BoxFuture<'a, Result<Box<dyn FileWriter>, ShellError>> {
+        Box::pin(future::ready(self.write_file(path, append)))
+    }
+
+    fn complete_path<'a>(&'a self, partial: &'a str) -> BoxFuture<'a, Vec<String>> {
+        Box::pin(future::ready(self.matching_paths(partial)))
+    }
+
+    fn executables(&self) -> BoxFuture<'_, Vec<String>> {
+        Box::pin(future::ready(self.path_executables()))
+    }
+
+    fn spawn_job(&self, job: BoxFuture<'static, ()>) -> Result<(), ShellError> {
+        thread::Builder::new()
+            .name(String::from("job"))
+            .spawn(move || futures_lite::future::block_on(job))?;
+
+        Ok(())
+    }
+
+    fn watch_stream(&self, stream: OutputStream, timeout: Duration) -> WatchedStream {
+        // The thread only runs one value ahead. It stops once the next value can't be handed
+        // over anymore.
+        let (sender, receiver) = mpsc::sync_channel(0);
+        let producer = thread::spawn(move || {
+            for value in stream {
+                if sender.send(value).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Box::new(Watched {
+            receiver: Some(receiver),
+            producer: Some(producer),
+            timeout,
+        })
+    }
+
+    fn signal(&self, process: &Process, signal: Signal) -> Result<(), ShellError> {
+        // It has already been reaped, so the id may belong to another process by now.
+        if !process.is_running() {
+            return Ok(());
+        }
+        send_signal(process.id, signal)
+    }
+
+    fn interrupt_on_ctrl_c(&self, ctrl_c: Arc<AtomicBool>) {
+        catch_interrupts(ctrl_c);
+    }
+
+    fn run_external(
+        &self,
+        command: &ExternalCommand,
+        input: Option<InputStream>,
+        is_last: bool,
+        errors: Option<Box<dyn FileWriter>>,
+    ) -> BoxFuture<'_, Result<ExternalOutput, ShellError>> {
+        Box::pin(future::ready(self.spawn(command, input, is_last, errors)))
+    }
+}
+
+impl StdShell {
+    /// Where the history is kept between sessions, `~/.config/queen-shell/history.txt` on Linux.
+    fn history_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("queen-shell").join("history.txt"))
+    }
+
+    fn read_history(&self) -> Result<Vec<String>, ShellError> {
+        let path = match Self::history_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(vec![]),
+        };
+
+        Ok(fs::read_to_string(path)?
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+
+    fn write_history(&self, entries: Vec<String>) -> Result<(), ShellError> {
+        let path = match Self::history_path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut contents = entries.join("\n");
+        contents.push('\n');
+
+        Ok(fs::write(path, contents)?)
+    }
+
+    fn read_file(&self, path: &str) -> Result<InputStream, ShellError> {
+        let file = fs::File::open(path)
+            .map_err(|err| ShellError::runtime_error(format!("{}: {}", path, err)))?;
+
+        Ok(OutputStream::new(
+            BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .map(Value::String),
+        ))
+    }
+
+    fn write_file(&self, path: &str, append: bool) -> Result<Box<dyn FileWriter>, ShellError> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .map_err(|err| ShellError::runtime_error(format!("{}: {}", path, err)))?;
+
+        Ok(Box::new(file))
+    }
+
+    fn matching_paths(&self, partial: &str) -> Vec<String> {
+        let (dir, prefix) = match partial.rfind('/') {
+            Some(i) => partial.split_at(i + 1),
+            None => ("", partial),
+        };
+        let entries = match fs::read_dir(if dir.is_empty() { "." } else { dir }) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+
+        entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let name = entry.file_name().into_string().ok()?;
+                // Hidden files only show up once their leading dot has been typed.
+                if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.'))
+                {
+                    return None;
+                }
+                let slash = if entry.path().is_dir() { "/" } else { "" };
+                Some(format!("{}{}{}", dir, name, slash))
+            })
+            .collect()
+    }
+
+    fn path_executables(&self) -> Vec<String> {
+        let paths = match env::var_os("PATH") {
+            Some(paths) => paths,
+            None => return vec![],
+        };
+
+        env::split_paths(&paths)
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                if !is_executable(&entry.path()) {
+                    return None;
+                }
+                entry.file_name().into_string().ok()
+            })
+            .collect()
+    }
+
+    fn list(
+        &self,
+        LsArgs { path, all }: LsArgs,
         context: &RunnableContext,
-    ) -> Result<Option<Vec<Value>>, ShellError> {
+    ) -> Result<OutputStream, ShellError> {
         let ctrl_c = context.ctrl_c.clone();
+        // Whether the contents of a directory are listed, rather than what a pattern matches
+        let mut whole_dir = true;
         let path = match path {
             None => {
-                if is_dir_empty(&self.path().into()) {
-                    return Ok(None);
+                if is_dir_empty(Path::new(&self.path())) {
+                    return Ok(OutputStream::empty());
                 } else {
                     PathBuf::from("./*")
                 }
@@ -59,41 +327,45 @@ impl Shell for StdShell {
                 let mut p: PathBuf = p.into();
                 if p.is_dir() {
                     if is_dir_empty(&p) {
-                        return Ok(None);
+                        return Ok(OutputStream::empty());
                     }
                     p.push("*");
+                } else {
+                    whole_dir = false;
                 }
                 p
             }
         };
 
-        let mut paths = match glob::glob(&path.to_string_lossy()) {
+        let options = glob::MatchOptions {
+            require_literal_leading_dot: !all,
+            ..glob::MatchOptions::new()
+        };
+        let mut paths = match glob::glob_with(&path.to_string_lossy(), options) {
             Ok(g) => Ok(g),
             Err(_) => Err(ShellError::runtime_error("Invalid File or Pattern")),
         }?
         .peekable();
         if paths.peek().is_none() {
-            return Err(ShellError::runtime_error("Invalid File or Pattern"));
-        }
-
-        let mut results = vec![];
-        for path in paths.flatten() {
-            if ctrl_c.load(Ordering::Acquire) {
-                break;
-            }
-            if let Some(name) = path.file_name().and_then(OsStr::to_str) {
-                results.push(Value::String(format!("{}: {}", get_path_type(&path), name)));
+            // A directory that only holds hidden files
+            if whole_dir {
+                return Ok(OutputStream::empty());
             }
+            return Err(ShellError::runtime_error("Invalid File or Pattern"));
         }
 
-        if results.is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(results))
-        }
+        Ok(OutputStream::new(
+            paths
+                .flatten()
+                .take_while(move |_| !ctrl_c.load(Ordering::Acquire))
+                .filter_map(|path| {
+                    let name = path.file_name().and_then(OsStr::to_str)?;
+                    Some(Value::Record(dir_entry(&path, name)))
+                }),
+        ))
     }
 
-    fn cd(&self, args: CdArgs) -> Result<Option<Vec<Value>>, ShellError> {
+    fn change_dir(&self, args: CdArgs) -> Result<OutputStream, ShellError> {
         let target: PathBuf = match args.dst {
             None => dirs::home_dir()
                 .ok_or_else(|| ShellError::runtime_error("Can not change to home directory"))?,
@@ -103,19 +375,28 @@ impl Shell for StdShell {
         if target.exists() && !target.is_dir() {
             return Err(ShellError::runtime_error(format!(
                 "{} is not a directory",
-                target.to_string_lossy().to_string()
+                target.to_string_lossy()
             )));
         }
 
         let path = PathBuf::from(self.path());
-        env::set_current_dir(path.join(&target)).expect("cannot to set current directory");
-        Ok(None)
+        env::set_current_dir(path.join(&target)).map_err(|err| {
+            ShellError::runtime_error(format!(
+                "can't change to {}: {}",
+                target.to_string_lossy(),
+                err
+            ))
+        })?;
+        Ok(OutputStream::empty())
     }
 
-    fn mkdir(
+    fn make_dirs(
         &self,
-        MkDirArgs { rest: directories }: MkDirArgs,
-    ) -> Result<Option<Vec<Value>>, ShellError> {
+        MkDirArgs {
+            rest: directories,
+            parents,
+        }: MkDirArgs,
+    ) -> Result<OutputStream, ShellError> {
         let full_path = PathBuf::from(self.path());
         for dir in directories {
             let create_at = {
@@ -124,24 +405,311 @@ impl Shell for StdShell {
                 loc
             };
 
-            let dir_res = std::fs::create_dir_all(create_at);
+            let dir_res = if parents {
+                std::fs::create_dir_all(create_at)
+            } else {
+                std::fs::create_dir(create_at)
+            };
             if let Err(reason) = dir_res {
                 return Err(ShellError::runtime_error(reason.to_string()));
             }
         }
-        Ok(None)
+        Ok(OutputStream::empty())
     }
 
-    fn path(&self) -> String {
-        current_dir()
-            .expect("can't get current directory")
-            .to_string_lossy()
-            .to_string()
+    fn spawn(
+        &self,
+        command: &ExternalCommand,
+        input: Option<InputStream>,
+        is_last: bool,
+        errors: Option<Box<dyn FileWriter>>,
+    ) -> Result<ExternalOutput, ShellError> {
+        let program = find_executable(&command.name).ok_or_else(|| {
+            ShellError::labeled_error(
+                format!("{}: command not found", command.name),
+                command.name_span,
+            )
+        })?;
+
+        let mut process = process::Command::new(program);
+        process.args(command.args.iter()).current_dir(self.path());
+        if input.is_some() {
+            process.stdin(Stdio::piped());
+        }
+        if !is_last {
+            process.stdout(Stdio::piped());
+        }
+        if errors.is_some() {
+            process.stderr(Stdio::piped());
+        }
+        let mut child = process
+            .spawn()
+            .map_err(|e| ShellError::labeled_error(e.to_string(), command.name_span))?;
+
+        // Feed stdin from another thread, so a child that fills its stdout pipe before it has
+        // read all of its input can't deadlock us.
+        let writer = match (input, child.stdin.take()) {
+            (Some(input), Some(mut stdin)) => Some(thread::spawn(move || {
+                for value in input {
+                    if writeln!(stdin, "{}", value).is_err() {
+                        break;
+                    }
+                }
+            })),
+            _ => None,
+        };
+        let error_writer = match (errors, child.stderr.take()) {
+            (Some(mut errors), Some(stderr)) => Some(thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    if errors.write_str(&line).and(errors.write_str("\n")).is_err() {
+                        break;
+                    }
+                }
+            })),
+            _ => None,
+        };
+
+        if let Some(stdout) = child.stdout.take() {
+            let process = Process {
+                id: child.id(),
+                exit: ExitCode::default(),
+            };
+            let lines = ChildLines {
+                child,
+                lines: BufReader::new(stdout).lines(),
+                exit: process.exit.clone(),
+                done: false,
+            };
+            return Ok(ExternalOutput::Stream(OutputStream::new(lines), process));
+        }
+
+        let status = child.wait()?;
+        for writer in writer.into_iter().chain(error_writer) {
+            let _ = writer.join();
+        }
+
+        Ok(ExternalOutput::Exited(status.code().unwrap_or(-1)))
+    }
+}
+
+impl FileWriter for fs::File {
+    fn write_str(&mut self, text: &str) -> Result<(), ShellError> {
+        Ok(self.write_all(text.as_bytes())?)
+    }
+}
+
+/// The stdout lines of a running child. The child is waited for once its output ends, or killed
+/// and reaped if the stream is dropped before that.
+struct ChildLines {
+    child: process::Child,
+    lines: io::Lines<BufReader<process::ChildStdout>>,
+    exit: ExitCode,
+    done: bool,
+}
+
+impl Iterator for ChildLines {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        match self.lines.next() {
+            Some(Ok(line)) => Some(Value::String(line)),
+            _ if self.done => None,
+            _ => {
+                self.done = true;
+                let status = self
+                    .child
+                    .wait()
+                    .map_or(-1, |status| status.code().unwrap_or(-1));
+                self.exit.set(status);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for ChildLines {
+    fn drop(&mut self) {
+        // Nobody is listening anymore if the stream was dropped early.
+        if !self.done {
+            let _ = self.child.kill();
+        }
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(unix)]
+fn send_signal(id: u32, signal: Signal) -> Result<(), ShellError> {
+    let pid = id as libc::pid_t;
+    let number = match signal {
+        Signal::Kill => libc::SIGKILL,
+        Signal::Stop => libc::SIGSTOP,
+        Signal::Continue => libc::SIGCONT,
+    };
+    if unsafe { libc::kill(pid, number) } != 0 {
+        let err = io::Error::last_os_error();
+        // It exited in the meantime.
+        if err.raw_os_error() == Some(libc::ESRCH) {
+            return Ok(());
+        }
+        return Err(err.into());
+    }
+
+    // Wait for the signal to take effect, leaving the process to be reaped by its `ChildLines`.
+    // Nothing is reported for a process that continues unless it was stopped, so that isn't
+    // waited for.
+    let options = match signal {
+        Signal::Kill => libc::WEXITED,
+        Signal::Stop => libc::WSTOPPED | libc::WEXITED,
+        Signal::Continue => return Ok(()),
+    };
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    // It fails once the process has been reaped, which means it is gone anyway.
+    unsafe { libc::waitid(libc::P_PID, id, &mut info, options | libc::WNOWAIT) };
+
+    Ok(())
+}
+
+/// The flag that a `SIGINT` sets, which is the foreground pipeline's `ctrl_c`
+#[cfg(unix)]
+static INTERRUPTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Makes a `SIGINT` set `ctrl_c` instead of ending the shell. The processes it starts get the
+/// default action back when they exec, so a ctrl-c still ends them.
+#[cfg(unix)]
+fn catch_interrupts(ctrl_c: Arc<AtomicBool>) {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        if let Some(ctrl_c) = INTERRUPTED.get() {
+            ctrl_c.store(true, Ordering::Release);
+        }
+    }
+
+    if INTERRUPTED.set(ctrl_c).is_ok() {
+        let handler = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        unsafe { libc::signal(libc::SIGINT, handler) };
+    }
+}
+
+#[cfg(not(unix))]
+fn catch_interrupts(_ctrl_c: Arc<AtomicBool>) {}
+
+#[cfg(not(unix))]
+fn send_signal(_id: u32, _signal: Signal) -> Result<(), ShellError> {
+    Err(ShellError::runtime_error(
+        "processes can only be signalled on unix",
+    ))
+}
+
+/// The values of a stream that is produced on a thread of its own, see `Shell::watch_stream`.
+struct Watched {
+    receiver: Option<mpsc::Receiver<Value>>,
+    producer: Option<thread::JoinHandle<()>>,
+    timeout: Duration,
+}
+
+impl Iterator for Watched {
+    type Item = Option<Value>;
+
+    fn next(&mut self) -> Option<Option<Value>> {
+        match self.receiver.as_ref()?.recv_timeout(self.timeout) {
+            Ok(value) => Some(Some(value)),
+            Err(RecvTimeoutError::Timeout) => Some(None),
+            Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+impl Drop for Watched {
+    fn drop(&mut self) {
+        // Whatever produced the stream, like the iteration of a loop, is done with before the
+        // shell carries on.
+        self.receiver.take();
+        if let Some(producer) = self.producer.take() {
+            let _ = producer.join();
+        }
+    }
+}
+
+/// Puts the terminal into raw mode for as long as it is alive, so that the line editor sees every
+/// key press and does its own echoing.
+struct RawMode {
+    #[cfg(unix)]
+    original: libc::termios,
+}
+
+impl RawMode {
+    /// Returns `None` when stdin isn't a terminal.
+    #[cfg(unix)]
+    fn enable() -> Option<Self> {
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+            return None;
+        }
+        let original = termios;
+        // Output processing stays on, so `\n` still moves to the start of the next line.
+        termios.c_iflag &= !(libc::ICRNL | libc::IXON | libc::BRKINT | libc::ISTRIP);
+        termios.c_lflag &= !(libc::ECHO | libc::ICANON | libc::IEXTEN | libc::ISIG);
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &termios) } != 0 {
+            return None;
+        }
+
+        Some(Self { original })
+    }
+
+    #[cfg(not(unix))]
+    fn enable() -> Option<Self> {
+        None
     }
 }
 
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original) };
+    }
+}
+
+#[cfg(unix)]
+fn terminal_size() -> Option<usize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+#[cfg(not(unix))]
+fn terminal_size() -> Option<usize> {
+    None
+}
+
+/// Resolves `name` to a program, searching `PATH` unless it already names a path.
+fn find_executable(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
 #[inline]
-fn is_dir_empty(d: &PathBuf) -> bool {
+fn is_dir_empty(d: &Path) -> bool {
     match d.read_dir() {
         Err(_e) => true,
         Ok(mut s) => s.next().is_none(),
@@ -149,12 +717,61 @@ fn is_dir_empty(d: &PathBuf) -> bool {
 }
 
 #[inline]
-fn get_path_type(d: &PathBuf) -> &str {
+fn get_path_type(d: &Path) -> &str {
     if d.is_dir() {
-        "d"
+        "dir"
     } else if d.is_file() {
-        "f"
+        "file"
     } else {
-        " "
+        ""
+    }
+}
+
+fn dir_entry(path: &Path, name: &str) -> Record {
+    let metadata = path.metadata().ok();
+    let size = match &metadata {
+        Some(metadata) if metadata.is_file() => Value::Int(metadata.len().into()),
+        _ => Value::Nothing,
+    };
+    let modified = metadata
+        .and_then(|metadata| metadata.modified().ok())
+        .map(|time| Value::String(format_time(time)))
+        .unwrap_or(Value::Nothing);
+
+    indexmap::indexmap! {
+        String::from("name") => Value::String(name.to_string()),
+        String::from("type") => Value::String(get_path_type(path).to_string()),
+        String::from("size") => size,
+        String::from("modified") => modified,
     }
 }
+
+/// Formats `time` as a UTC `YYYY-MM-DD hh:mm:ss` timestamp.
+fn format_time(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    };
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,90 @@
+use crate::{
+    commands::{CdArgs, LsArgs, MkDirArgs, RunnableContext},
+    error::ShellError,
+    stream::OutputStream,
+    BoxFuture,
+};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{future::Future, pin::Pin};
+use spin::RwLock;
+
+use super::Shell;
+
+/// A shell for unit tests, with a fixed set of paths and programs. What it prints is kept, rather
+/// than shown.
+#[derive(Debug, Default)]
+pub(crate) struct TestShell {
+    pub paths: Vec<String>,
+    pub executables: Vec<String>,
+    pub printed: RwLock<String>,
+}
+
+impl TestShell {
+    pub fn with_paths(mut self, paths: &[&str]) -> Self {
+        self.paths = paths.iter().map(|path| String::from(*path)).collect();
+        self
+    }
+
+    pub fn with_executables(mut self, names: &[&str]) -> Self {
+        self.executables = names.iter().map(|name| String::from(*name)).collect();
+        self
+    }
+}
+
+fn unsupported<'a>() -> BoxFuture<'a, Result<OutputStream, ShellError>> {
+    Box::pin(core::future::ready(Err(ShellError::runtime_error(
+        "the test shell has no file system",
+    ))))
+}
+
+impl Shell for TestShell {
+    fn name(&self) -> &str {
+        "Test shell"
+    }
+
+    fn homedir(&self) -> Option<String> {
+        Some(String::from("/home/test"))
+    }
+
+    fn read_input(&self) -> Pin<Box<dyn Future<Output = Vec<u8>> + '_>> {
+        Box::pin(core::future::ready(vec![]))
+    }
+
+    fn print(&self, s: &str) {
+        self.printed.write().push_str(s);
+    }
+
+    fn ls(
+        &self,
+        _args: LsArgs,
+        _context: &RunnableContext,
+    ) -> BoxFuture<'_, Result<OutputStream, ShellError>> {
+        unsupported()
+    }
+
+    fn cd(&self, _args: CdArgs) -> BoxFuture<'_, Result<OutputStream, ShellError>> {
+        unsupported()
+    }
+
+    fn mkdir(&self, _args: MkDirArgs) -> BoxFuture<'_, Result<OutputStream, ShellError>> {
+        unsupported()
+    }
+
+    fn path(&self) -> String {
+        String::from("/test")
+    }
+
+    fn complete_path<'a>(&'a self, partial: &'a str) -> BoxFuture<'a, Vec<String>> {
+        let paths = self
+            .paths
+            .iter()
+            .filter(|path| path.starts_with(partial))
+            .cloned()
+            .collect();
+        Box::pin(core::future::ready(paths))
+    }
+
+    fn executables(&self) -> BoxFuture<'_, Vec<String>> {
+        Box::pin(core::future::ready(self.executables.clone()))
+    }
+}
@@ -8,15 +8,22 @@ use indexmap::IndexMap;
 /// The types of named parameter that a command can have
 #[derive(Debug, Clone)]
 pub enum NamedType {
-    /// A flag without any associated argument. eg) `foo --bar`
-    Switch,
+    /// A flag without any associated argument. eg) `foo --bar` or `foo -b`
+    Switch(Option<char>),
     /// A mandatory flag, with associated argument. eg) `foo --required xyz`
-    Mandatory(SyntaxShape),
+    Mandatory(SyntaxShape, Option<char>),
     /// An optional flag, with associated argument. eg) `foo --optional abc`
-    Optional(SyntaxShape),
+    Optional(SyntaxShape, Option<char>),
 }
 
 impl NamedType {
+    /// The character that stands for the flag after a single dash, if it has one
+    pub fn short(&self) -> Option<char> {
+        match self {
+            Self::Switch(short) | Self::Mandatory(_, short) | Self::Optional(_, short) => *short,
+        }
+    }
+
     /// Returns `true` if the named_type is [`Optional`].
     pub fn is_optional(&self) -> bool {
         matches!(self, Self::Optional(..))
@@ -98,6 +105,8 @@ pub struct Signature {
     pub positional: Vec<(PositionalType, Description)>,
     /// After the positional arguments, a catch-all for the rest of the arguments that might follow, their type, and help text
     pub rest_positional: Option<(SyntaxShape, Description)>,
+    /// Whether the rest of the arguments is taken as one string, exactly as it was typed
+    pub rest_as_typed: bool,
     /// The named flags with corresponding type and help text
     pub named: IndexMap<String, (NamedType, Description)>,
 }
@@ -110,6 +119,14 @@ impl Signature {
     pub fn remove_named(&mut self, name: &str) {
         self.named.remove(name);
     }
+
+    /// Finds the flag that `short` stands for, returning its long name and type
+    pub fn short_named(&self, short: char) -> Option<(&str, &NamedType)> {
+        self.named
+            .iter()
+            .find(|(_, (kind, _))| kind.short() == Some(short))
+            .map(|(name, (kind, _))| (name.as_str(), kind))
+    }
 }
 
 impl Signature {
@@ -120,6 +137,7 @@ impl Signature {
             usage: String::new(),
             positional: vec![],
             rest_positional: None,
+            rest_as_typed: false,
             named: indexmap::indexmap! {},
         }
     }
@@ -171,9 +189,12 @@ impl Signature {
         name: impl Into<String>,
         ty: impl Into<SyntaxShape>,
         desc: impl Into<String>,
+        short: Option<char>,
     ) -> Signature {
-        self.named
-            .insert(name.into(), (NamedType::Optional(ty.into()), desc.into()));
+        self.named.insert(
+            name.into(),
+            (NamedType::Optional(ty.into(), short), desc.into()),
+        );
 
         self
     }
@@ -184,17 +205,25 @@ impl Signature {
         name: impl Into<String>,
         ty: impl Into<SyntaxShape>,
         desc: impl Into<String>,
+        short: Option<char>,
     ) -> Signature {
-        self.named
-            .insert(name.into(), (NamedType::Mandatory(ty.into()), desc.into()));
+        self.named.insert(
+            name.into(),
+            (NamedType::Mandatory(ty.into(), short), desc.into()),
+        );
 
         self
     }
 
     /// Add a switch to the signature
-    pub fn switch(mut self, name: impl Into<String>, desc: impl Into<String>) -> Signature {
+    pub fn switch(
+        mut self,
+        name: impl Into<String>,
+        desc: impl Into<String>,
+        short: Option<char>,
+    ) -> Signature {
         self.named
-            .insert(name.into(), (NamedType::Switch, desc.into()));
+            .insert(name.into(), (NamedType::Switch(short), desc.into()));
         self
     }
 
@@ -203,4 +232,21 @@ impl Signature {
         self.rest_positional = Some((ty, desc.into()));
         self
     }
+
+    /// Takes the rest of the command, from the first word after the positional arguments to its
+    /// end, as one string exactly as it was typed, flags and all
+    pub fn rest_as_typed(mut self, desc: impl Into<String>) -> Signature {
+        self.rest_positional = Some((SyntaxShape::String, desc.into()));
+        self.rest_as_typed = true;
+        self
+    }
+
+    /// Gets the expected type of the positional argument at `index`, which is the type of the
+    /// rest arguments once the declared positionals run out
+    pub fn positional_shape(&self, index: usize) -> Option<SyntaxShape> {
+        self.positional
+            .get(index)
+            .map(|(positional, _)| positional.syntax_type())
+            .or_else(|| self.rest_positional.as_ref().map(|(shape, _)| *shape))
+    }
 }
@@ -0,0 +1,244 @@
+use crate::{error::ShellError, evaluate::Value};
+use alloc::{boxed::Box, sync::Arc, task::Wake, vec::Vec};
+use core::{
+    fmt,
+    future::Future,
+    iter,
+    pin::pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll, Waker},
+};
+use spin::RwLock;
+
+/// The values flowing out of a command, produced lazily so that the next stage (or the renderer)
+/// can start working on the first value before the last one exists.
+///
+/// Whether the commands behind the stream succeeded can only be known once it has run out, eg an
+/// external command only has an exit status once its output ends. They record their failures in
+/// the stream's `Failures`, for whoever drains it to check.
+pub struct OutputStream {
+    values: Box<dyn Iterator<Item = Value> + Send>,
+    failures: Failures,
+}
+
+/// Where the producer of a stream records that it failed, once the stream has run out.
+#[derive(Debug, Clone, Default)]
+pub struct Failure(Arc<RwLock<Option<ShellError>>>);
+
+impl Failure {
+    pub fn set(&self, err: ShellError) {
+        *self.0.write() = Some(err);
+    }
+
+    pub fn get(&self) -> Option<ShellError> {
+        self.0.read().clone()
+    }
+}
+
+/// The failures of all the commands behind a stream.
+#[derive(Debug, Clone, Default)]
+pub struct Failures(Vec<Failure>);
+
+impl Failures {
+    pub fn push(&mut self, failure: Failure) {
+        self.0.push(failure);
+    }
+
+    pub fn extend(&mut self, other: Failures) {
+        self.0.extend(other.0);
+    }
+
+    /// Returns the first failure that was recorded, which is only final once the stream has been
+    /// drained.
+    pub fn check(&self) -> Result<(), ShellError> {
+        match self.0.iter().find_map(Failure::get) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The values flowing into a command from the stage before it.
+pub type InputStream = OutputStream;
+
+impl OutputStream {
+    pub fn new(values: impl Iterator<Item = Value> + Send + 'static) -> Self {
+        Self {
+            values: Box::new(values),
+            failures: Failures::default(),
+        }
+    }
+
+    /// Attaches `failure` for the consumer to check once it has drained the stream.
+    pub fn with_failure(mut self, failure: Failure) -> Self {
+        self.failures.push(failure);
+        self
+    }
+
+    /// Attaches `failures` for the consumer to check once it has drained the stream.
+    pub fn with_failures(mut self, failures: Failures) -> Self {
+        self.failures.extend(failures);
+        self
+    }
+
+    /// Passes the failure of the stream through `f` once it has run out, eg to point it somewhere
+    /// else.
+    pub fn map_failure(self, f: impl Fn(ShellError) -> ShellError + Send + 'static) -> Self {
+        let failures = self.failures;
+        let failure = Failure::default();
+        let mapped = failure.clone();
+        let end = iter::from_fn(move || {
+            if let Err(err) = failures.check() {
+                mapped.set(f(err));
+            }
+            None
+        });
+
+        Self::new(self.values.chain(end)).with_failure(failure)
+    }
+
+    /// The failures recorded for the stream, to check once it has been drained.
+    #[inline]
+    pub fn failures(&self) -> Failures {
+        self.failures.clone()
+    }
+
+    #[inline]
+    pub fn empty() -> Self {
+        Self::new(iter::empty())
+    }
+
+    #[inline]
+    pub fn one(value: Value) -> Self {
+        Self::new(iter::once(value))
+    }
+
+    /// Drains the stream, waiting for every value to be produced.
+    #[inline]
+    pub fn into_vec(self) -> Vec<Value> {
+        self.collect()
+    }
+}
+
+impl Iterator for OutputStream {
+    type Item = Value;
+
+    #[inline]
+    fn next(&mut self) -> Option<Value> {
+        self.values.next()
+    }
+}
+
+impl From<Vec<Value>> for OutputStream {
+    fn from(values: Vec<Value>) -> Self {
+        Self::new(values.into_iter())
+    }
+}
+
+impl Default for OutputStream {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl fmt::Debug for OutputStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OutputStream")
+    }
+}
+
+/// Wakes `block_on` up.
+struct Flag {
+    woken: AtomicBool,
+    /// The thread that `block_on` parks until the future is woken
+    #[cfg(any(test, feature = "std"))]
+    thread: std::thread::Thread,
+}
+
+impl Flag {
+    fn new() -> Self {
+        Self {
+            woken: AtomicBool::new(false),
+            #[cfg(any(test, feature = "std"))]
+            thread: std::thread::current(),
+        }
+    }
+
+    /// Waits until the flag is raised, and lowers it again.
+    fn wait(&self) {
+        while !self.woken.swap(false, Ordering::AcqRel) {
+            #[cfg(any(test, feature = "std"))]
+            std::thread::park();
+            #[cfg(not(any(test, feature = "std")))]
+            core::hint::spin_loop();
+        }
+    }
+}
+
+impl Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        #[cfg(any(test, feature = "std"))]
+        self.thread.unpark();
+    }
+}
+
+/// Runs `future` to completion on the current thread, for a stream that has to await something to
+/// produce its next values, like the next iteration of a loop.
+///
+/// The thread is parked until the future is woken. `no_std` has no way to park it, so there it
+/// spins instead, which the futures of commands hardly ever make it do.
+pub fn block_on<T>(future: impl Future<Output = T>) -> T {
+    let mut future = pin!(future);
+    let flag = Arc::new(Flag::new());
+    let waker = Waker::from(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        flag.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Duration};
+
+    /// Pending until another thread wakes it
+    struct Later {
+        done: Arc<AtomicBool>,
+        started: bool,
+    }
+
+    impl Future for Later {
+        type Output = ();
+
+        fn poll(mut self: core::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.done.load(Ordering::Acquire) {
+                return Poll::Ready(());
+            }
+            if !self.started {
+                self.started = true;
+                let (done, waker) = (self.done.clone(), cx.waker().clone());
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(20));
+                    done.store(true, Ordering::Release);
+                    waker.wake();
+                });
+            }
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn block_on_waits_to_be_woken() {
+        let done = Arc::new(AtomicBool::new(false));
+        block_on(Later {
+            done: done.clone(),
+            started: false,
+        });
+        assert!(done.load(Ordering::Acquire));
+    }
+}